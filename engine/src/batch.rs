@@ -0,0 +1,264 @@
+use simxiv_prelude::SimError;
+use crate::Engine;
+use std::thread;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistogramBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize
+}
+
+#[derive(Clone, Debug)]
+pub struct DpsDistribution {
+    samples: Vec<f64>
+}
+
+impl DpsDistribution {
+    pub fn new(mut samples: Vec<f64>) -> Self {
+        samples.sort_by(|a, b| a.total_cmp(b));
+        Self {
+            samples
+        }
+    }
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+    pub fn mean(&self) -> f64 {
+        match self.samples.len() {
+            0 => 0.0,
+            len => self.samples.iter().sum::<f64>() / len as f64
+        }
+    }
+    pub fn std_dev(&self) -> f64 {
+        if self.samples.len() < 2 {
+            return 0.0
+        }
+        let mean = self.mean();
+        let squares:f64 = self.samples.iter().map(|sample| (sample - mean).powi(2)).sum();
+        (squares / (self.samples.len() - 1) as f64).sqrt()
+    }
+    pub fn min(&self) -> f64 {
+        self.samples.first().cloned().unwrap_or(0.0)
+    }
+    pub fn max(&self) -> f64 {
+        self.samples.last().cloned().unwrap_or(0.0)
+    }
+    // Linear interpolation between the closest ranks, with p in [0, 100]
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0) * (self.samples.len() - 1) as f64;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        self.samples[lower] + (self.samples[upper] - self.samples[lower]) * (rank - lower as f64)
+    }
+    pub fn median(&self) -> f64 {
+        self.percentile(50.0)
+    }
+    // Splits [min, max] into equally sized bins; the last bin includes the maximum
+    pub fn histogram(&self, bins: usize) -> Vec<HistogramBin> {
+        if bins == 0 || self.samples.is_empty() {
+            return vec![]
+        }
+        let (min, max) = (self.min(), self.max());
+        let width = (max - min) / bins as f64;
+        let mut histogram:Vec<HistogramBin> = (0..bins).map(|i| HistogramBin {
+            lower: min + width * i as f64,
+            upper: min + width * (i + 1) as f64,
+            count: 0
+        }).collect();
+        for sample in self.samples.iter() {
+            let index = match width > 0.0 {
+                true => (((sample - min) / width) as usize).min(bins - 1),
+                false => 0
+            };
+            histogram[index].count += 1;
+        }
+        histogram
+    }
+}
+
+type RunResult = Result<Vec<(String, f64)>, SimError>;
+
+#[derive(Clone, Debug)]
+pub struct EntityDps {
+    pub name: String,
+    pub dps: DpsDistribution
+}
+
+pub struct BatchReport {
+    pub runs: usize,
+    // In the order the setup added the entities
+    pub entities: Vec<EntityDps>
+}
+
+impl BatchReport {
+    // The first entity by that name; entities sharing a name are told apart with dps_at
+    pub fn dps_of(&self, name: &str) -> Option<&DpsDistribution> {
+        self.entities.iter().find(|entity| entity.name == name).map(|entity| &entity.dps)
+    }
+    pub fn dps_at(&self, index: usize) -> Option<&DpsDistribution> {
+        self.entities.get(index).map(|entity| &entity.dps)
+    }
+}
+
+// Runs the same encounter many times over consecutive seeds. Entities are matched across runs by the order
+// the setup added them in, since every run creates fresh ids.
+pub struct BatchRunner<F> where F: Fn(u64) -> Engine + Sync {
+    setup: F,
+    runs: usize,
+    first_seed: u64,
+    threads: usize
+}
+
+impl<F> BatchRunner<F> where F: Fn(u64) -> Engine + Sync {
+    pub fn new(runs: usize, setup: F) -> Self {
+        Self {
+            setup,
+            runs,
+            first_seed: 0,
+            threads: thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1)
+        }
+    }
+    pub fn with_first_seed(self, first_seed: u64) -> Self {
+        Self {
+            first_seed,
+            ..self
+        }
+    }
+    pub fn with_threads(self, threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            ..self
+        }
+    }
+    fn run_one(&self, seed: u64) -> RunResult {
+        let mut engine = (self.setup)(seed);
+        let duration = engine.run()?.as_secs_f64();
+        Ok(engine.order.iter().filter_map(|id| engine.entities.get(id)).map(|entity| {
+            let damage = engine.damage.total_by_source(&entity.id) as f64;
+            let dps = match duration > 0.0 {
+                true => damage / duration,
+                false => 0.0
+            };
+            (entity.name.clone(), dps)
+        }).collect())
+    }
+    pub fn run(&self) -> Result<BatchReport, SimError> {
+        let threads = self.threads.min(self.runs.max(1));
+        // Each thread takes every n-th run; results are put back in seed order afterwards
+        let results:Vec<Vec<(usize, RunResult)>> = thread::scope(|scope| {
+            let handles:Vec<_> = (0..threads).map(|offset| scope.spawn(move || {
+                (offset..self.runs).step_by(threads)
+                    .map(|run| (run, self.run_one(self.first_seed.wrapping_add(run as u64))))
+                    .collect::<Vec<_>>()
+            })).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        let mut results:Vec<(usize, RunResult)> = results.into_iter().flatten().collect();
+        results.sort_by_key(|(run, _)| *run);
+        let mut entities:Vec<(String, Vec<f64>)> = vec![];
+        for (_, result) in results {
+            for (index, (name, dps)) in result?.into_iter().enumerate() {
+                if index == entities.len() {
+                    entities.push((name, vec![]));
+                }
+                entities[index].1.push(dps);
+            }
+        }
+        Ok(BatchReport {
+            runs: self.runs,
+            entities: entities.into_iter().map(|(name, samples)| EntityDps {
+                name,
+                dps: DpsDistribution::new(samples)
+            }).collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchRunner, DpsDistribution, HistogramBin};
+    use crate::{Engine, EndCondition};
+    use simxiv_prelude::{ConditionalAction, Job, Entity, Action, Effect, Moment, SkillType, DamageType, Element};
+    use std::sync::Arc;
+
+    fn encounter(seed: u64) -> Engine {
+        let mut engine = Engine::from_seed(seed)
+            .with_end_condition(EndCondition::TimeLimit(Moment::new(60, 0)));
+        let mut red_mage = Entity::create("red_mage".to_string(), Some(Job::RDM), 70, vec![
+            ConditionalAction::Cast {
+                spell: 1,
+                selector: Arc::new(Box::new(|_, targets| {
+                    targets.into_iter().find(|target| target.name == "big_bad").map(|target| target.id)
+                }))
+            }
+        ], Arc::new(vec![
+            Action::new(1, Moment::new(2, 500))
+                .with_effects(|source, targets| targets.into_iter().map(|target| Effect::Damage {
+                    source: source.clone(),
+                    target: target.clone(),
+                    action: 1,
+                    potency: 300,
+                    skill_type: SkillType::Spell,
+                    r#type: DamageType::Magic(Element::Unaspected),
+                    periodic: false
+                }).collect())
+        ]));
+        red_mage.set_statistic("Magic Attack Power", 2011);
+        red_mage.set_statistic("Magic Damage", 105);
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![])));
+        engine
+    }
+
+    #[test]
+    fn distribution_statistics() {
+        let distribution = DpsDistribution::new(vec![4.0, 1.0, 3.0, 2.0, 5.0]);
+        assert_eq!(distribution.mean(), 3.0);
+        assert!((distribution.std_dev() - 2.5f64.sqrt()).abs() < 1e-9);
+        assert_eq!(distribution.min(), 1.0);
+        assert_eq!(distribution.max(), 5.0);
+        assert_eq!(distribution.median(), 3.0);
+        assert_eq!(distribution.percentile(25.0), 2.0);
+        assert_eq!(distribution.percentile(90.0), 4.6);
+        assert_eq!(distribution.histogram(2), vec![
+            HistogramBin { lower: 1.0, upper: 3.0, count: 2 },
+            HistogramBin { lower: 3.0, upper: 5.0, count: 3 }
+        ]);
+    }
+
+    #[test]
+    fn distribution_survives_nan() {
+        let distribution = DpsDistribution::new(vec![2.0, f64::NAN, 1.0]);
+        assert_eq!(distribution.min(), 1.0);
+        assert_eq!(distribution.samples().len(), 3);
+    }
+
+    #[test]
+    fn entities_sharing_a_name_are_kept_apart() {
+        let report = BatchRunner::new(2, |seed| {
+            let mut engine = encounter(seed);
+            engine.add_entity(Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![])));
+            engine
+        }).run().ok().unwrap();
+        assert_eq!(report.entities.iter().map(|entity| entity.name.as_str()).collect::<Vec<&str>>(), vec!["red_mage", "big_bad", "big_bad"]);
+        assert_eq!(report.dps_at(2).unwrap().samples().len(), 2);
+        assert!(report.dps_at(0).unwrap().min() > 0.0);
+    }
+
+    #[test]
+    fn runs_are_reproducible_across_thread_counts() {
+        let parallel = BatchRunner::new(16, encounter).with_first_seed(100).with_threads(4).run().ok().unwrap();
+        let serial = BatchRunner::new(16, encounter).with_first_seed(100).with_threads(1).run().ok().unwrap();
+        let red_mage = parallel.dps_of("red_mage").unwrap();
+        assert_eq!(parallel.runs, 16);
+        assert_eq!(red_mage.samples().len(), 16);
+        assert_eq!(red_mage.samples(), serial.dps_of("red_mage").unwrap().samples());
+        assert!(red_mage.min() > 0.0);
+        assert!(red_mage.min() <= red_mage.mean() && red_mage.mean() <= red_mage.max());
+        assert_eq!(parallel.dps_of("big_bad").unwrap().max(), 0.0);
+    }
+}
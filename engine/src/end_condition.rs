@@ -0,0 +1,22 @@
+use simxiv_prelude::Moment;
+use crate::Engine;
+
+pub enum EndCondition {
+    AllHostilesDead,
+    TimeLimit(Moment),
+    Predicate(Box<dyn Fn(&Engine) -> bool>)
+}
+
+impl EndCondition {
+    pub fn is_met(&self, engine: &Engine) -> bool {
+        match self {
+            EndCondition::AllHostilesDead => {
+                let mut hostiles = engine.entities.values().filter(|entity| entity.hostile).peekable();
+                // A fight with nobody to kill never ends this way
+                hostiles.peek().is_some() && hostiles.all(|entity| !entity.is_alive())
+            },
+            EndCondition::TimeLimit(limit) => &engine.current_time >= limit,
+            EndCondition::Predicate(predicate) => predicate(engine)
+        }
+    }
+}
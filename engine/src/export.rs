@@ -0,0 +1,259 @@
+use simxiv_prelude::DefenseRoll;
+use simxiv_spelldata::{ActionId, RawAction};
+use crate::{Engine, LogEntry, CombatEvent};
+use uuid::Uuid;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+
+// One flat row per log entry, so that the same shape works for JSON Lines and CSV
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ExportRow {
+    pub s: i32,
+    pub ms: i32,
+    pub event: &'static str,
+    pub source: Option<String>,
+    pub target: Option<String>,
+    pub action: Option<u32>,
+    pub action_name: Option<String>,
+    pub aura: Option<u32>,
+    pub resource: Option<String>,
+    pub amount: Option<i64>,
+    pub outcome: Option<&'static str>,
+    pub defense: Option<&'static str>
+}
+
+impl ExportRow {
+    fn new(entry: &LogEntry, event: &'static str) -> Self {
+        Self {
+            s: entry.time.seconds(),
+            ms: entry.time.millis(),
+            event,
+            source: None,
+            target: None,
+            action: None,
+            action_name: None,
+            aura: None,
+            resource: None,
+            amount: None,
+            outcome: None,
+            defense: None
+        }
+    }
+}
+
+pub struct LogExporter<'a> {
+    names: HashMap<Uuid, String>,
+    actions: &'a HashMap<ActionId, RawAction>
+}
+
+impl<'a> LogExporter<'a> {
+    pub fn new(engine: &Engine, actions: &'a HashMap<ActionId, RawAction>) -> Self {
+        Self {
+            names: engine.entities.values().map(|entity| (entity.id, entity.name.clone())).collect(),
+            actions
+        }
+    }
+    fn name(&self, id: &Uuid) -> Option<String> {
+        Some(self.names.get(id).cloned().unwrap_or_else(|| id.to_string()))
+    }
+    fn action_name(&self, id: u32) -> Option<String> {
+        self.actions.get(&id).map(|action| action.name.clone())
+    }
+    pub fn row(&self, entry: &LogEntry) -> ExportRow {
+        match &entry.event {
+            CombatEvent::CastStart { source, target, action, .. } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                action: Some(*action),
+                action_name: self.action_name(*action),
+                ..ExportRow::new(entry, "cast_start")
+            },
+            CombatEvent::CastFinish { source, target, action } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                action: Some(*action),
+                action_name: self.action_name(*action),
+                ..ExportRow::new(entry, "cast_finish")
+            },
+            CombatEvent::ActionReady { entity, action } => ExportRow {
+                source: self.name(entity),
+                action: Some(*action),
+                action_name: self.action_name(*action),
+                ..ExportRow::new(entry, "action_ready")
+            },
+            CombatEvent::AnimationLock { entity, action, .. } => ExportRow {
+                source: self.name(entity),
+                action: Some(*action),
+                action_name: self.action_name(*action),
+                ..ExportRow::new(entry, "animation_lock")
+            },
+            CombatEvent::Idle { entity } => ExportRow {
+                source: self.name(entity),
+                ..ExportRow::new(entry, "idle")
+            },
+            CombatEvent::Clip { entity, action, lost } => ExportRow {
+                source: self.name(entity),
+                action: Some(*action),
+                action_name: self.action_name(*action),
+                amount: Some(lost.as_millis()),
+                ..ExportRow::new(entry, "clip")
+            },
+            CombatEvent::AuraApplied { source, target, aura, .. } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                aura: Some(*aura),
+                ..ExportRow::new(entry, "aura_applied")
+            },
+            CombatEvent::AuraRemoved { source, target, aura } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                aura: Some(*aura),
+                ..ExportRow::new(entry, "aura_removed")
+            },
+            CombatEvent::AuraExpired { source, target, aura } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                aura: Some(*aura),
+                ..ExportRow::new(entry, "aura_expired")
+            },
+            CombatEvent::AuraStacks { source, target, aura, stacks } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                aura: Some(*aura),
+                amount: Some(i64::from(*stacks)),
+                ..ExportRow::new(entry, "aura_stacks")
+            },
+            CombatEvent::Damage { source, target, action, amount, critical, direct, defense, periodic } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                action: Some(*action),
+                // Periodic damage is keyed on the aura that carries it rather than on an action
+                action_name: match periodic {
+                    true => None,
+                    false => self.action_name(*action)
+                },
+                amount: Some(i64::from(*amount)),
+                outcome: Some(match (critical, direct) {
+                    (true, true) => "critical_direct_hit",
+                    (true, false) => "critical_hit",
+                    (false, true) => "direct_hit",
+                    (false, false) => "hit"
+                }),
+                defense: Some(match defense {
+                    DefenseRoll::Dodge => "dodge",
+                    DefenseRoll::Parry => "parry",
+                    DefenseRoll::Block => "block",
+                    DefenseRoll::Hit => "hit"
+                }),
+                ..ExportRow::new(entry, match periodic {
+                    true => "tick",
+                    false => "damage"
+                })
+            },
+            CombatEvent::Heal { source, target, amount } => ExportRow {
+                source: self.name(source),
+                target: self.name(target),
+                amount: Some(i64::from(*amount)),
+                ..ExportRow::new(entry, "heal")
+            },
+            CombatEvent::ResourceChange { entity, resource, amount } => ExportRow {
+                target: self.name(entity),
+                resource: Some(resource.clone()),
+                amount: Some(i64::from(*amount)),
+                ..ExportRow::new(entry, "resource_change")
+            },
+            CombatEvent::Death { entity } => ExportRow {
+                target: self.name(entity),
+                ..ExportRow::new(entry, "death")
+            }
+        }
+    }
+    pub fn write_json_lines<W: Write>(&self, entries: &[LogEntry], mut writer: W) -> Result<(), Box<dyn Error>> {
+        for entry in entries {
+            serde_json::to_writer(&mut writer, &self.row(entry))?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+    pub fn write_csv<W: Write>(&self, entries: &[LogEntry], writer: W) -> Result<(), Box<dyn Error>> {
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        for entry in entries {
+            csv_writer.serialize(self.row(entry))?;
+        }
+        csv_writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogExporter;
+    use crate::Engine;
+    use simxiv_prelude::{ConditionalAction, Job, Entity, Action, Effect, Moment, SkillType, DamageType, Element};
+    use simxiv_spelldata::load_actions;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn veraero_fight() -> Engine {
+        let mut engine = Engine::from_seed(11);
+        let mut red_mage = Entity::create("red_mage".to_string(), Some(Job::RDM), 70, vec![
+            ConditionalAction::Cast {
+                spell: 7507,
+                selector: Arc::new(Box::new(|_, targets| {
+                    targets.into_iter().find(|target| target.name == "big_bad").map(|target| target.id)
+                }))
+            }
+        ], Arc::new(vec![
+            Action::new(7507, Moment::new(5, 0))
+                .with_effects(|source, targets| targets.into_iter().map(|target| Effect::Damage {
+                    source: source.clone(),
+                    target: target.clone(),
+                    action: 7507,
+                    potency: 300,
+                    skill_type: SkillType::Spell,
+                    r#type: DamageType::Magic(Element::Unaspected),
+                    periodic: false
+                }).collect())
+        ]));
+        red_mage.set_statistic("Magic Attack Power", 2011);
+        red_mage.set_statistic("Magic Damage", 105);
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![])));
+        engine.run_until(Moment::new(6, 0)).ok().unwrap();
+        engine
+    }
+
+    #[test]
+    fn exports_json_lines_and_csv() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../spell_data/tests/action.csv");
+        let actions = load_actions(path).unwrap();
+        let engine = veraero_fight();
+        let exporter = LogExporter::new(&engine, &actions);
+
+        let mut json = vec![];
+        exporter.write_json_lines(engine.log(), &mut json).unwrap();
+        let lines:Vec<serde_json::Value> = String::from_utf8(json).unwrap().lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), engine.log().len());
+        let damage = lines.iter().find(|line| line["event"] == "damage").unwrap();
+        assert_eq!(damage["s"], 5);
+        assert_eq!(damage["ms"], 0);
+        assert_eq!(damage["source"], "red_mage");
+        assert_eq!(damage["target"], "big_bad");
+        assert_eq!(damage["action"], 7507);
+        assert_eq!(damage["action_name"], "Veraero");
+        assert_eq!(damage["amount"].as_u64(), Some(engine.damage.total()));
+        assert!(damage["outcome"].is_string());
+
+        let mut csv = vec![];
+        exporter.write_csv(engine.log(), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut rows = csv.lines();
+        assert_eq!(rows.next(), Some("s,ms,event,source,target,action,action_name,aura,resource,amount,outcome,defense"));
+        assert_eq!(rows.next(), Some("0,0,cast_start,red_mage,big_bad,7507,Veraero,,,,,"));
+        assert_eq!(rows.count(), engine.log().len() - 1);
+    }
+}
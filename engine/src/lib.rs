@@ -1,101 +1,559 @@
 extern crate uuid;
 extern crate simxiv_prelude;
+extern crate rand;
+extern crate simxiv_spelldata;
+extern crate serde_json;
+extern crate csv;
+#[macro_use] extern crate serde_derive;
 use uuid::Uuid;
 use std::borrow::BorrowMut;
-use simxiv_prelude::{Moment, Entity, Aura, SimError, Action, Status, Effect};
-use std::collections::HashMap;
+use std::sync::Arc;
+use simxiv_prelude::{Moment, Entity, Aura, AuraCatalogue, RemovalMode, SimError, Status, Effect, DamageStrategy, AssumedDamageStrategy, SeededRandom, SharedRandom, AttackRoll};
+use std::collections::{HashMap, HashSet};
 
+mod scheduler;
+mod meter;
+mod end_condition;
+mod batch;
+mod log;
+mod export;
+mod weights;
+
+pub use scheduler::{Scheduler, Event};
+pub use meter::{DamageMeter, DamageKey, DamageRecord};
+pub use end_condition::EndCondition;
+pub use batch::{BatchRunner, BatchReport, EntityDps, DpsDistribution, HistogramBin};
+pub use log::{CombatLog, CombatEvent, LogEntry};
+pub use export::{LogExporter, ExportRow};
+pub use weights::{StatWeightCalculator, StatWeights};
 
 pub struct Engine {
     pub entities: HashMap<Uuid, Entity>,
-    pub current_time: Moment
+    pub current_time: Moment,
+    pub damage: DamageMeter,
+    damage_strategy: Box<dyn DamageStrategy>,
+    seed: u64,
+    prng: SharedRandom,
+    end_conditions: Vec<EndCondition>,
+    log: CombatLog,
+    scheduler: Scheduler,
+    order: Vec<Uuid>,
+    clipped: HashMap<Uuid, Moment>,
+    auras: AuraCatalogue,
+    // Server ticks land every 3 seconds from this moment; one is only scheduled while something needs it
+    server_tick: Moment,
+    tick_pending: bool,
+    // Entities that took an action from idle at the current moment, and those of them waiting for the next one
+    acted: HashSet<Uuid>,
+    deferred: HashSet<Uuid>,
+    // Entities whose swing timer is running; it stops while they have nobody to swing at
+    swinging: HashSet<Uuid>
 }
 
+const SERVER_TICK_MS: i64 = 3000;
+
 impl Engine {
     pub fn new() -> Self {
+        // Even unseeded runs pick their seed up front so they can be replayed
+        Self::from_seed(rand::random())
+    }
+    pub fn from_seed(seed: u64) -> Self {
+        let prng = SeededRandom::shared(seed);
+        let phase = (prng.lock().unwrap().gen_f64() * SERVER_TICK_MS as f64) as i64;
         Self {
             entities: HashMap::new(),
-            current_time: Moment::new(0, 0)
+            current_time: Moment::new(0, 0),
+            damage: DamageMeter::new(),
+            damage_strategy: Box::new(AssumedDamageStrategy::with_random(Arc::clone(&prng))),
+            seed,
+            prng,
+            end_conditions: vec![],
+            log: CombatLog::new(),
+            scheduler: Scheduler::new(),
+            order: vec![],
+            clipped: HashMap::new(),
+            auras: AuraCatalogue::new(),
+            server_tick: Moment::from_millis(phase),
+            tick_pending: false,
+            acted: HashSet::new(),
+            deferred: HashSet::new(),
+            swinging: HashSet::new()
+        }
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    // Custom damage strategies should roll from this so the whole run follows the engine's seed
+    pub fn prng(&self) -> SharedRandom {
+        Arc::clone(&self.prng)
+    }
+    pub fn with_damage_strategy(self, strategy: Box<dyn DamageStrategy>) -> Self {
+        Self {
+            damage_strategy: strategy,
+            ..self
+        }
+    }
+    pub fn with_aura_catalogue(self, auras: AuraCatalogue) -> Self {
+        Self {
+            auras,
+            ..self
+        }
+    }
+    pub fn server_tick_phase(&self) -> Moment {
+        self.server_tick.clone()
+    }
+    pub fn with_end_condition(mut self, condition: EndCondition) -> Self {
+        self.end_conditions.push(condition);
+        self
+    }
+    pub fn subscribe(&mut self, subscriber: impl FnMut(&LogEntry) + 'static) {
+        self.log.subscribe(Box::new(subscriber));
+    }
+    pub fn log(&self) -> &[LogEntry] {
+        self.log.entries()
+    }
+    // Time the entity's GCD sat ready while it was still locked by an off-GCD action
+    pub fn clipped_time(&self, entity: &Uuid) -> Moment {
+        self.clipped.get(entity).cloned().unwrap_or_default()
+    }
+    pub fn is_over(&self) -> bool {
+        self.end_conditions.iter().any(|condition| condition.is_met(self))
+    }
+    fn time_limit(&self) -> Option<Moment> {
+        self.end_conditions.iter().filter_map(|condition| match condition {
+            EndCondition::TimeLimit(limit) => Some(limit.clone()),
+            _ => None
+        }).min()
+    }
+    // Runs until an end condition is met or nothing is left to happen, and returns when the fight ended
+    pub fn run(&mut self) -> Result<Moment, SimError> {
+        while !self.is_over() {
+            let next = match self.scheduler.next_time() {
+                Some(next) => next,
+                None => break
+            };
+            if let Some(limit) = self.time_limit() {
+                if next >= limit {
+                    self.current_time = limit;
+                    break;
+                }
+            }
+            self.step()?;
         }
+        Ok(self.current_time.clone())
     }
     pub fn add_entity(&mut self, e: Entity) {
+        // The first swing goes out on the pull, before anything else the entity does. A newcomer may also be
+        // somebody to swing at for entities whose timer stopped
+        if e.auto_attack.is_some() {
+            self.swinging.insert(e.id);
+            self.scheduler.schedule(self.current_time.clone(), Event::AutoAttack { entity: e.id });
+        }
+        for id in self.order.clone() {
+            let stopped = self.entities[&id].auto_attack.is_some() && self.entities[&id].is_alive();
+            if stopped && self.swinging.insert(id) {
+                self.scheduler.schedule(self.current_time.clone(), Event::AutoAttack { entity: id });
+            }
+        }
+        self.scheduler.schedule(self.current_time.clone(), Event::Wake { entity: e.id });
+        if e.regenerates() {
+            let now = self.current_time.clone();
+            self.schedule_server_tick(&now);
+        }
+        // Auras put up before the entity joined still have to come off
+        let now = self.current_time.clone();
+        let mut auras:Vec<&Aura> = e.auras.values().flatten().collect();
+        auras.sort_by_key(|aura| aura.id);
+        for aura in auras {
+            self.schedule_aura(&now, aura);
+        }
+        self.order.push(e.id);
         self.entities.insert(e.id, e);
     }
+    // Moves the clock forward by the interval, resolving every event scheduled before the new time
     pub fn crank_by(&mut self, interval: Moment) -> Result<(), SimError> {
-        let mut new_entities:HashMap<Uuid, Entity>;
-        let new_time = self.current_time.clone();
-        // Go through our entities, see what they will do next
-        let effects:Result<Vec<Effect>, SimError> = self.entities.iter().fold(Ok(vec![]), |mut state, (id, ref entity)| {
-            state.and_then(|mut current_effects| {
-                entity.effects_at(new_time.clone(), &self.entities).map(|mut effects| {
-                    current_effects.append(&mut effects);
-                    current_effects
-                })
-            })
+        let until = self.current_time.clone() + interval;
+        self.run_until(until)
+    }
+    pub fn run_until(&mut self, until: Moment) -> Result<(), SimError> {
+        while let Some(next) = self.scheduler.next_time() {
+            if next >= until {
+                break;
+            }
+            self.step()?;
+        }
+        if until > self.current_time {
+            self.current_time = until;
+        }
+        Ok(())
+    }
+    // Jumps straight to the next scheduled moment and resolves everything that happens at it.
+    // Returns the moment that was processed, or None once nothing is left to happen.
+    pub fn step(&mut self) -> Result<Option<Moment>, SimError> {
+        let time = match self.scheduler.next_time() {
+            Some(time) => time,
+            None => return Ok(None)
+        };
+        self.current_time = time.clone();
+        self.acted.clear();
+        self.deferred.clear();
+        loop {
+            while let Some((_, event)) = self.scheduler.pop_until(&time) {
+                // Server ticks resolve first and still see the auras that fall off at this moment; nothing else does
+                if event != Event::ServerTick {
+                    self.expire_auras(&time);
+                }
+                self.handle_event(time.clone(), event)?;
+            }
+            self.expire_auras(&time);
+            // Anything that happened may have made an action available to an idle entity
+            if !self.wake_idle(&time)? {
+                break;
+            }
+        }
+        Ok(Some(time))
+    }
+    fn handle_event(&mut self, time: Moment, event: Event) -> Result<(), SimError> {
+        let effects = match event {
+            Event::Wake { entity } => self.wake(&time, entity)?,
+            Event::ActionReady { entity, action } => {
+                self.log.push(time.clone(), CombatEvent::ActionReady {
+                    entity,
+                    action
+                });
+                self.wake(&time, entity)?
+            },
+            Event::AutoAttack { entity } => self.auto_attack(&time, entity),
+            // The expiry pass in step already took the aura off; the event only makes sure a step happens then
+            Event::AuraExpiry { .. } => vec![],
+            Event::ServerTick => {
+                self.tick_pending = false;
+                let effects:Vec<Effect> = self.order.iter()
+                    .filter_map(|id| self.entities.get(id))
+                    .flat_map(|entity| entity.process_dots(time.clone()).into_iter().chain(entity.process_regen()))
+                    .collect();
+                self.process_effects(time.clone(), effects)?;
+                // The tick keeps going while something still ticks, or some resource is not back to where it settles
+                if self.entities.values().any(|entity| entity.has_periodic_auras(&time) || entity.regenerates()) {
+                    self.schedule_server_tick(&time);
+                }
+                vec![]
+            }
+        };
+        self.process_effects(time, effects)
+    }
+    // Casts hold the swing back until they finish; the dead, and those with nobody left to hit, stop swinging
+    fn auto_attack(&mut self, time: &Moment, id: Uuid) -> Vec<Effect> {
+        let entity = match self.entities.get(&id) {
+            Some(entity) if entity.is_alive() => entity,
+            _ => {
+                self.swinging.remove(&id);
+                return vec![]
+            }
+        };
+        if let Status::Casting { ref end_time, .. } = entity.status {
+            if end_time > time {
+                self.scheduler.schedule(end_time.clone(), Event::AutoAttack { entity: id });
+                return vec![]
+            }
+        }
+        // Without a target the timer stops, ready to swing as soon as add_entity brings a new one
+        let effects = entity.auto_attack_effects(&self.entities);
+        match (effects.is_empty(), entity.auto_delay()) {
+            (false, Some(delay)) => {
+                self.scheduler.schedule(time.clone() + delay, Event::AutoAttack { entity: id });
+                self.entities.get_mut(&id).unwrap().last_auto = time.clone();
+            },
+            _ => {
+                self.swinging.remove(&id);
+            }
+        }
+        effects
+    }
+    // An entity takes at most one action from idle per moment, or an action with no lock and no recast would be
+    // used over and over without the clock moving. Anything more it wants to do waits for the next millisecond
+    fn wake(&mut self, time: &Moment, id: Uuid) -> Result<Vec<Effect>, SimError> {
+        let entity = match self.entities.get(&id) {
+            Some(entity) => entity,
+            None => return Ok(vec![])
+        };
+        let idle = matches!(entity.status, Status::Idle { .. });
+        let effects = entity.effects_at(time.clone(), &self.entities)?;
+        if !idle || effects.is_empty() {
+            return Ok(effects)
+        }
+        if self.acted.insert(id) {
+            return Ok(effects)
+        }
+        if self.deferred.insert(id) {
+            self.scheduler.schedule(time.clone() + Moment::from_millis(1), Event::Wake { entity: id });
+        }
+        Ok(vec![])
+    }
+    fn wake_idle(&mut self, time: &Moment) -> Result<bool, SimError> {
+        let mut acted = false;
+        for id in self.order.clone() {
+            let effects = match self.entities.get(&id).map(|entity| &entity.status) {
+                Some(Status::Idle { .. }) => self.wake(time, id)?,
+                _ => vec![]
+            };
+            if !effects.is_empty() {
+                acted = true;
+                self.process_effects(time.clone(), effects)?;
+            }
+        }
+        Ok(acted)
+    }
+    fn expire_auras(&mut self, time: &Moment) {
+        for id in self.order.clone() {
+            let expired = match self.entities.get_mut(&id) {
+                Some(entity) => entity.cleanup(time.clone()),
+                None => continue
+            };
+            for aura in expired {
+                self.log.push(time.clone(), CombatEvent::AuraExpired {
+                    source: aura.source.id,
+                    target: id,
+                    aura: aura.id
+                });
+            }
+        }
+    }
+    // The aura may have been on the entity for a while already, when an application extended it
+    fn schedule_aura(&mut self, time: &Moment, aura: &Aura) {
+        self.scheduler.schedule(aura.end_time.clone(), Event::AuraExpiry {
+            target: aura.target.id,
+            aura: aura.id
         });
-        
-        // At this point we have every effect that will happen. This means that we can safely use our entity map.
-        effects.and_then(|e| {
-            self.process_effects(new_time.clone(), e)
-        }).map(|_| {
-            // We're done with this iteration. Let's allow the entities to clear their internal state
-            self.entities.iter_mut().map(|(k, mut e)| e.cleanup(new_time.clone()));
-            self.current_time = self.current_time.clone() + interval
-        })
+        if aura.effects.iter().any(|effect| effect.is_periodic()) {
+            self.schedule_server_tick(time);
+        }
+    }
+    fn consume_stacks(&mut self, time: &Moment, source: Uuid, target: Uuid, aura: u32, stacks: u32) {
+        let definition = self.auras.definition(&aura);
+        let target_entity = self.entities.get_mut(&target).unwrap();
+        if let Some(left) = target_entity.consume_stacks(&aura, source, stacks, &definition) {
+            self.log.push(time.clone(), match left {
+                0 => CombatEvent::AuraRemoved { source, target, aura },
+                _ => CombatEvent::AuraStacks { source, target, aura, stacks: left }
+            });
+        }
+    }
+    // Schedules the first server tick strictly after the moment, unless one is already on its way
+    fn schedule_server_tick(&mut self, after: &Moment) {
+        if self.tick_pending {
+            return;
+        }
+        let phase = self.server_tick.as_millis();
+        let ticks = (after.as_millis() - phase).div_euclid(SERVER_TICK_MS) + 1;
+        self.scheduler.schedule(Moment::from_millis(phase + ticks * SERVER_TICK_MS), Event::ServerTick);
+        self.tick_pending = true;
     }
     pub fn process_effects(&mut self, time:Moment, effects: Vec<Effect>) -> Result<(), SimError> {
         effects.into_iter().fold(Ok(()), |state, effect| {
             state.and_then(|_| {
                 if let Effect::BeginIdle { ref target, ref start } = &effect {
-                    let mut target_entity = self.entities.get_mut(&target.id).unwrap();
-                    println!("{}: Target {} begins to idle", time, target.name);
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
                     target_entity.set_status(Status::Idle {
                         start_time: start.clone()
                     });
+                    self.scheduler.schedule(start.clone(), Event::Wake { entity: target.id });
+                    self.log.push(time.clone(), CombatEvent::Idle {
+                        entity: target.id
+                    });
                 }
-                if let Effect::ApplyAura { ref source, ref target, ref aura, ref duration } = &effect {
-                    println!("{}: Target {} applies aura {} on {}", time, source.name, aura, target.name);
-                    let mut target_entity = self.entities.get_mut(&target.id).unwrap();
-                    target_entity.add_aura(Aura {
-                        id: aura.clone(),
+                if let Effect::ApplyAura { ref source, ref target, ref aura, ref duration, ref effects } = &effect {
+                    // The catalogue says what the aura does and how it stacks; the effect can add to what it does
+                    let definition = self.auras.definition(aura);
+                    let mut aura_effects = definition.effects.clone();
+                    aura_effects.extend(effects.iter().cloned());
+                    let new_aura = Aura {
+                        id: *aura,
                         source: source.clone(),
                         target: target.clone(),
                         start_time: time.clone(),
                         end_time: time.clone() + duration.clone(),
-                        effects: vec![]
-                    })
+                        effects: aura_effects,
+                        stacks: definition.initial_stacks
+                    };
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
+                    if let Some(applied) = target_entity.add_aura(new_aura, &definition) {
+                        self.schedule_aura(&time, &applied);
+                        self.log.push(time.clone(), CombatEvent::AuraApplied {
+                            source: source.id,
+                            target: target.id,
+                            aura: *aura,
+                            duration: applied.end_time - time.clone()
+                        });
+                    }
+                }
+                if let Effect::RemoveAura { ref target, ref aura, ref mode } = &effect {
+                    match mode {
+                        RemovalMode::Stack(stack_source) => self.consume_stacks(&time, *stack_source, target.id, *aura, 1),
+                        _ => {
+                            let definition = self.auras.definition(aura);
+                            let target_entity = self.entities.get_mut(&target.id).unwrap();
+                            for removed_aura in target_entity.remove_aura(aura, mode.clone(), &definition) {
+                                self.log.push(time.clone(), CombatEvent::AuraRemoved {
+                                    source: removed_aura.source.id,
+                                    target: target.id,
+                                    aura: *aura
+                                });
+                            }
+                        }
+                    }
+                }
+                if let Effect::Cleanse { ref target, ref kind } = &effect {
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
+                    for removed_aura in target_entity.cleanse(kind, &self.auras) {
+                        self.log.push(time.clone(), CombatEvent::AuraRemoved {
+                            source: removed_aura.source.id,
+                            target: target.id,
+                            aura: removed_aura.id
+                        });
+                    }
+                }
+                if let Effect::AddStacks { ref source, ref target, ref aura, ref stacks } = &effect {
+                    let definition = self.auras.definition(aura);
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
+                    if let Some(left) = target_entity.add_stacks(aura, source.id, *stacks, &definition) {
+                        self.log.push(time.clone(), CombatEvent::AuraStacks {
+                            source: source.id,
+                            target: target.id,
+                            aura: *aura,
+                            stacks: left
+                        });
+                    }
+                }
+                if let Effect::ConsumeStacks { ref source, ref target, ref aura, ref stacks } = &effect {
+                    self.consume_stacks(&time, source.id, target.id, *aura, *stacks);
                 }
-                if let Effect::RemoveAura { ref source, ref target, ref aura } = &effect {
-                    let mut target_entity = self.entities.get_mut(&target.id).unwrap();
-                    target_entity.remove_aura(aura, Some(source.id))
+                if let Effect::Damage { ref source, ref target, ref action, ref periodic, .. } = &effect {
+                    // Periodic damage snapshotted its source when the aura went up; direct hits use the source as it is now
+                    let dealer = match periodic {
+                        true => source.clone(),
+                        false => self.entities.get(&source.id).cloned().unwrap_or_else(|| source.clone())
+                    };
+                    let target_entity = self.entities.get(&target.id).unwrap();
+                    if !target_entity.is_alive() {
+                        return Ok(())
+                    }
+                    let raw_damage = self.damage_strategy.deal_damage(&dealer, effect.clone());
+                    let applied = self.damage_strategy.apply_damage(target_entity, raw_damage);
+                    let (critical, direct) = match applied.attack_roll {
+                        AttackRoll::CriticalHit(direct) => (true, direct),
+                        AttackRoll::Hit(direct) => (false, direct)
+                    };
+                    self.log.push(time.clone(), CombatEvent::Damage {
+                        source: source.id,
+                        target: target.id,
+                        action: *action,
+                        amount: applied.value,
+                        critical,
+                        direct,
+                        defense: applied.defense_roll.clone(),
+                        periodic: *periodic
+                    });
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
+                    target_entity.take_damage(applied.value);
+                    if !target_entity.is_alive() {
+                        self.log.push(time.clone(), CombatEvent::Death {
+                            entity: target.id
+                        });
+                    }
+                    self.damage.record(time.clone(), source.id, target.id, *action, applied);
                 }
-                if let Effect::Damage { ref source, ref target, ref action, ref potency, ref skill_type, ref r#type, ref periodic } = &effect {
-                    return Ok(())
+                if let Effect::Heal { ref source, ref target, ref amount } = &effect {
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
+                    target_entity.heal(*amount);
+                    self.log.push(time.clone(), CombatEvent::Heal {
+                        source: source.id,
+                        target: target.id,
+                        amount: *amount
+                    });
                 }
                 if let Effect::ModifyResource { ref target, ref resource, ref amount } = &effect {
-                    let mut target_entity = self.entities.get_mut(&target.id).unwrap();
-                    println!("{}: Target {} gained {} {}", time, target.name, resource, amount);
-                    target_entity.modify_resource(resource.to_string(), amount.clone());
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
+                    target_entity.modify_resource(resource.to_string(), *amount);
+                    let regenerates = target_entity.regenerates();
+                    self.log.push(time.clone(), CombatEvent::ResourceChange {
+                        entity: target.id,
+                        resource: resource.clone(),
+                        amount: *amount
+                    });
+                    if regenerates {
+                        self.schedule_server_tick(&time);
+                    }
+                }
+                if let Effect::StartRecast { ref source, ref action, ref start } = &effect {
+                    // Only the global cooldown is shortened by speed and haste
+                    let action = match action.off_gcd {
+                        true => action.clone(),
+                        false => {
+                            let recast = self.damage_strategy.scale_by_speed(&self.entities[&source.id], action.recast_time.clone());
+                            action.clone().with_recast_time(recast)
+                        }
+                    };
+                    let source_entity = self.entities.get_mut(&source.id).unwrap();
+                    let ready = source_entity.start_recast(&action, start.clone());
+                    if &ready > start {
+                        self.scheduler.schedule(ready, Event::ActionReady {
+                            entity: source.id,
+                            action: action.id
+                        });
+                    }
                 }
                 if let Effect::BeginCast { ref source, ref target, ref action, ref duration } = &effect {
-                    println!("{}: Target {} begins to cast {} on {}", time, source.name, action.id, target.name);
-                    let mut target_entity = self.entities.get_mut(&source.id).unwrap();
+                    let duration = &self.damage_strategy.scale_by_speed(&self.entities[&source.id], duration.clone());
+                    let target_entity = self.entities.get_mut(&source.id).unwrap();
                     target_entity.set_status(Status::Casting {
                         source: Box::new(source.clone()),
                         target: Box::new(target.clone()),
                         spell: action.clone(),
                         start_time: time.clone(),
                         end_time: time.clone() + duration.clone()
-                    })
+                    });
+                    self.scheduler.schedule(time.clone() + duration.clone(), Event::Wake { entity: source.id });
+                    self.log.push(time.clone(), CombatEvent::CastStart {
+                        source: source.id,
+                        target: target.id,
+                        action: action.id,
+                        duration: duration.clone()
+                    });
+                }
+                if let Effect::FinishCast { ref source, ref target, ref action } = &effect {
+                    self.log.push(time.clone(), CombatEvent::CastFinish {
+                        source: source.id,
+                        target: target.id,
+                        action: action.id
+                    });
                 }
                 if let Effect::BeginAnimationLock { ref target, ref action, ref start, ref duration } = &effect {
-                    let mut target_entity = self.entities.get_mut(&target.id).unwrap();
-                    println!("{}: Target {} is animation locked from casting {} for {}", time, target.name, action.id, duration);
+                    let target_entity = self.entities.get_mut(&target.id).unwrap();
                     target_entity.set_status(Status::AnimationLocked {
                         action: action.clone(),
                         start_time: time.clone(),
                         end_time: time.clone() + duration.clone()
-                    })
+                    });
+                    self.scheduler.schedule(time.clone() + duration.clone(), Event::Wake { entity: target.id });
+                    self.log.push(time.clone(), CombatEvent::AnimationLock {
+                        entity: target.id,
+                        action: action.id,
+                        duration: duration.clone()
+                    });
+                    let lock_end = time.clone() + duration.clone();
+                    match target_entity.gcd_ready_at() {
+                        Some(ready) if action.off_gcd && ready < lock_end && target_entity.has_usable_gcd_action() => {
+                            let lost = lock_end - std::cmp::max(ready, time.clone());
+                            *self.clipped.entry(target.id).or_default() += lost.clone();
+                            self.log.push(time.clone(), CombatEvent::Clip {
+                                entity: target.id,
+                                action: action.id,
+                                lost
+                            });
+                        },
+                        _ => ()
+                    }
                 }
                 Ok(())
             })
@@ -105,8 +563,10 @@ impl Engine {
 
 #[cfg(test)]
 mod tests{
-    use simxiv_prelude::{ConditionalAction, Job, Status, Entity, Action, Effect, Moment};
-    use crate::Engine;
+    use simxiv_prelude::{ConditionalAction, Job, Status, Entity, Action, Effect, Moment, SkillType, DamageType, Element, Aura, AuraEffect, AutoAttack, AUTO_ATTACK, ExpectedDamageStrategy, AuraCatalogue, AuraDefinition, RemovalMode};
+    use crate::{Engine, EndCondition, CombatEvent};
+    use std::rc::Rc;
+    use std::cell::RefCell;
     use std::sync::Arc;
     use uuid::Uuid;
 
@@ -187,7 +647,8 @@ mod tests{
                             source: source.clone(),
                             target: source.clone(),
                             aura: 2,
-                            duration: Moment::new(12, 0)
+                            duration: Moment::new(12, 0),
+                            effects: vec![]
                         }
                     ]
                 }
@@ -203,9 +664,9 @@ mod tests{
         .with_effect_modifier(|source, target| {
             vec![
                 Effect::RemoveAura {
-                    source: source.clone(),
                     target: source.clone(),
-                    aura: 2
+                    aura: 2,
+                    mode: RemovalMode::Source(source.id)
                 }
             ]
         })
@@ -233,11 +694,68 @@ mod tests{
         engine.add_entity(big_bad);
         verify_states(engine, Moment::new(0, 100), vec![
             State(Moment::new(1,0), red_mage_id.clone(), "casting".to_string()),
-            State(Moment::new(2,750), red_mage_id.clone(), "locked".to_string()),
-            State(Moment::new(4,250), red_mage_id.clone(), "idle".to_string())
+            State(Moment::new(2,750), red_mage_id.clone(), "locked".to_string())
+        ])
+    }
+    #[test]
+    fn dualcast_timeline() {
+        let mut engine = Engine::new();
+        let red_mage = Entity::create("red_mage".to_string(), Some(Job::RDM), 70, vec![
+            ConditionalAction::Cast {
+                spell: 1,
+                selector: Arc::new(Box::new(|_, targets| {
+                    targets.into_iter().find(|target| target.name == "big_bad").map(|target| target.id)
+                }))
+            }
+        ], Arc::new(vec![
+            dualcast_generate(dualcast_consume(Action::new(1, Moment::new(2,500))
+                .with_animation_delay(Some(Moment::new(0, 750)))))
+        ]));
+        let red_mage_id = red_mage.id;
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        // Cast until 2.5, locked until 3.25, instant dualcast locked until 4.0, then the next hard cast
+        verify_states(engine, Moment::new(0, 100), vec![
+            State(Moment::new(1,0), red_mage_id, "casting".to_string()),
+            State(Moment::new(2,750), red_mage_id, "locked".to_string()),
+            State(Moment::new(3,400), red_mage_id, "locked".to_string()),
+            State(Moment::new(4,250), red_mage_id, "casting".to_string())
         ])
     }
     #[test]
+    fn steps_jump_to_exact_event_times() {
+        let mut engine = Engine::new();
+        let red_mage = Entity::create("red_mage".to_string(), Some(Job::RDM), 70, vec![
+            ConditionalAction::Cast {
+                spell: 1,
+                selector: Arc::new(Box::new(|source, targets| {
+                    targets.into_iter().filter(|target| {
+                        target.name == "big_bad".to_string()
+                    }).collect::<Vec<&Entity>>().first().map(|r| r.id.clone())
+                }))
+            }
+        ], Arc::new(vec![
+            dualcast_generate(dualcast_consume(Action::new(1, Moment::new(2,500))
+                .with_animation_delay(Some(Moment::new(0, 750)))))
+        ]));
+        let big_bad = Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![]));
+        engine.add_entity(red_mage);
+        engine.add_entity(big_bad);
+        let mut times = vec![];
+        while times.len() < 5 {
+            times.push(engine.step().ok().unwrap().unwrap());
+        }
+        assert_eq!(times, vec![
+            Moment::new(0, 0),
+            // Cast ends, the dualcast aura goes up and the caster is locked
+            Moment::new(2, 500),
+            // Lock ends and the instant dualcast goes straight out
+            Moment::new(3, 250),
+            Moment::new(4, 0),
+            Moment::new(6, 500)
+        ]);
+    }
+    #[test]
     fn test_dualcast() {
         let mut engine = Engine::new();
         let red_mage = Entity::create("red_mage".to_string(), Some(Job::RDM), 70, vec![
@@ -266,6 +784,735 @@ mod tests{
         ])
     }
 
+    fn nuking_red_mage() -> Entity {
+        let mut red_mage = Entity::create("red_mage".to_string(), Some(Job::RDM), 70, vec![
+            ConditionalAction::Cast {
+                spell: 1,
+                selector: Arc::new(Box::new(|_, targets| {
+                    targets.into_iter().filter(|target| {
+                        target.name == "big_bad".to_string()
+                    }).collect::<Vec<&Entity>>().first().map(|r| r.id.clone())
+                }))
+            }
+        ], Arc::new(vec![
+            Action::new(1, Moment::new(2, 500))
+                .with_animation_delay(Some(Moment::new(0, 750)))
+                .with_effects(|source, targets| targets.into_iter().map(|target| Effect::Damage {
+                    source: source.clone(),
+                    target: target.clone(),
+                    action: 1,
+                    potency: 300,
+                    skill_type: SkillType::Spell,
+                    r#type: DamageType::Magic(Element::Unaspected),
+                    periodic: false
+                }).collect())
+        ]));
+        red_mage.set_statistic("Magic Attack Power", 2011);
+        red_mage.set_statistic("Magic Damage", 105);
+        red_mage.set_statistic("Determination", 1834);
+        red_mage
+    }
+    #[test]
+    fn damage_goes_through_the_strategy_and_is_recorded() {
+        let mut engine = Engine::new();
+        let red_mage = nuking_red_mage();
+        let big_bad = Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![]));
+        let red_mage_id = red_mage.id.clone();
+        let big_bad_id = big_bad.id.clone();
+        engine.add_entity(red_mage);
+        engine.add_entity(big_bad);
+        engine.run_until(Moment::new(6, 0)).ok().unwrap();
+        // Two casts land, at 2.5s and 5.75s
+        let hits = engine.damage.hits(&red_mage_id, &big_bad_id, 1);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].time, Moment::new(2, 500));
+        assert!(hits.iter().all(|hit| hit.damage.value > 0));
+        let total = engine.damage.total_by_source(&red_mage_id);
+        assert_eq!(total, engine.damage.total_on_target(&big_bad_id));
+        assert_eq!(total, engine.damage.total_by_action(&red_mage_id, 1));
+        assert_eq!(engine.damage.total_by_source(&big_bad_id), 0);
+    }
+    #[test]
+    fn fight_ends_when_all_hostiles_are_dead() {
+        let mut engine = Engine::new()
+            .with_end_condition(EndCondition::AllHostilesDead)
+            .with_end_condition(EndCondition::TimeLimit(Moment::new(60, 0)));
+        let red_mage = nuking_red_mage();
+        let mut big_bad = Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![]));
+        big_bad.hostile = true;
+        big_bad.set_max_hp(1);
+        let big_bad_id = big_bad.id.clone();
+        engine.add_entity(red_mage);
+        engine.add_entity(big_bad);
+        assert_eq!(engine.run().ok(), Some(Moment::new(2, 500)));
+        assert_eq!(engine.entities.get(&big_bad_id).unwrap().hp(), Some(0));
+        assert!(!engine.entities.get(&big_bad_id).unwrap().is_alive());
+    }
+    #[test]
+    fn fight_ends_at_the_time_limit() {
+        let mut engine = Engine::new()
+            .with_end_condition(EndCondition::AllHostilesDead)
+            .with_end_condition(EndCondition::TimeLimit(Moment::new(10, 0)));
+        let mut big_bad = Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![]));
+        big_bad.hostile = true;
+        engine.add_entity(nuking_red_mage());
+        engine.add_entity(big_bad);
+        assert_eq!(engine.run().ok(), Some(Moment::new(10, 0)));
+        let mut engine = Engine::new()
+            .with_end_condition(EndCondition::Predicate(Box::new(|engine| engine.damage.total() > 0)));
+        engine.add_entity(nuking_red_mage());
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        assert_eq!(engine.run().ok(), Some(Moment::new(2, 500)));
+    }
+    #[test]
+    fn dead_entities_stop_acting() {
+        let mut engine = Engine::new();
+        let mut red_mage = nuking_red_mage();
+        red_mage.set_max_hp(100);
+        red_mage.take_damage(150);
+        red_mage.heal(50);
+        let red_mage_id = red_mage.id.clone();
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(10, 0)).ok().unwrap();
+        assert_eq!(engine.entities.get(&red_mage_id).unwrap().hp(), Some(0));
+        assert_eq!(engine.damage.total(), 0);
+    }
+
+    #[test]
+    fn same_seed_replays_bit_for_bit() {
+        let run = |seed| {
+            let mut engine = Engine::from_seed(seed)
+                .with_end_condition(EndCondition::TimeLimit(Moment::new(30, 0)));
+            let red_mage = nuking_red_mage();
+            let big_bad = Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![]));
+            let (red_mage_id, big_bad_id) = (red_mage.id.clone(), big_bad.id.clone());
+            engine.add_entity(red_mage);
+            engine.add_entity(big_bad);
+            engine.run().ok().unwrap();
+            engine.damage.hits(&red_mage_id, &big_bad_id, 1).iter().map(|hit| hit.damage.value).collect::<Vec<u32>>()
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+        assert_eq!(Engine::from_seed(7).seed(), 7);
+    }
+    #[test]
+    fn combat_log_records_typed_events() {
+        let mut engine = Engine::from_seed(3);
+        let red_mage = nuking_red_mage();
+        let big_bad = Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![]));
+        let (red_mage_id, big_bad_id) = (red_mage.id.clone(), big_bad.id.clone());
+        let seen = Rc::new(RefCell::new(0));
+        let counter = Rc::clone(&seen);
+        engine.subscribe(move |_| *counter.borrow_mut() += 1);
+        engine.add_entity(red_mage);
+        engine.add_entity(big_bad);
+        engine.run_until(Moment::new(3, 500)).ok().unwrap();
+        let events:Vec<(Moment, CombatEvent)> = engine.log().iter().map(|entry| (entry.time.clone(), entry.event.clone())).collect();
+        assert_eq!(events[0], (Moment::new(0, 0), CombatEvent::CastStart {
+            source: red_mage_id,
+            target: big_bad_id,
+            action: 1,
+            duration: Moment::new(2, 500)
+        }));
+        assert_eq!(events[1], (Moment::new(2, 500), CombatEvent::CastFinish {
+            source: red_mage_id,
+            target: big_bad_id,
+            action: 1
+        }));
+        match &events[2] {
+            (time, CombatEvent::Damage { source, target, action, amount, periodic, .. }) => {
+                assert_eq!(time, &Moment::new(2, 500));
+                assert_eq!((source, target, *action, *periodic), (&red_mage_id, &big_bad_id, 1, false));
+                assert_eq!(u64::from(*amount), engine.damage.total());
+            },
+            other => panic!("expected damage, got {:?}", other)
+        }
+        assert_eq!(events[3], (Moment::new(2, 500), CombatEvent::AnimationLock {
+            entity: red_mage_id,
+            action: 1,
+            duration: Moment::new(0, 750)
+        }));
+        assert_eq!(events[4], (Moment::new(3, 250), CombatEvent::Idle {
+            entity: red_mage_id
+        }));
+        assert_eq!(*seen.borrow(), engine.log().len());
+    }
+    fn hit(id: u32) -> impl Fn(&Entity, Vec<&Entity>) -> Vec<Effect> {
+        move |source, targets| targets.into_iter().map(|target| Effect::Damage {
+            source: source.clone(),
+            target: target.clone(),
+            action: id,
+            potency: 200,
+            skill_type: SkillType::Skill,
+            r#type: DamageType::Blunt,
+            periodic: false
+        }).collect()
+    }
+    fn monk(apl: Vec<u32>, actions: Vec<Action>) -> Entity {
+        let apl = apl.into_iter().map(|spell| ConditionalAction::Cast {
+            spell,
+            selector: Arc::new(Box::new(|_, targets: Vec<&Entity>| {
+                targets.into_iter().find(|target| target.name == "big_bad").map(|target| target.id)
+            }))
+        }).collect();
+        let mut monk = Entity::create("monk".to_string(), Some(Job::MNK), 70, apl, Arc::new(actions));
+        monk.set_statistic("Attack Power", 2011);
+        monk.set_statistic("Physical Damage", 105);
+        monk
+    }
+    fn weaving_monk() -> Entity {
+        monk(vec![10, 11], vec![
+            // A GCD weaponskill on a 2.5s recast
+            Action::new(10, Moment::new(0, 0))
+                .with_off_gcd(false)
+                .with_recast_time(Moment::new(2, 500))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(hit(10)),
+            // An off-GCD ability on a 30s recast
+            Action::new(11, Moment::new(0, 0))
+                .with_recast_time(Moment::new(30, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(hit(11))
+        ])
+    }
+    fn uses_of(engine: &Engine, entity: &Uuid) -> Vec<(Moment, u32)> {
+        engine.log().iter().filter_map(|entry| match entry.event {
+            CombatEvent::CastFinish { ref source, action, .. } if source == entity => Some((entry.time.clone(), action)),
+            _ => None
+        }).collect()
+    }
+    #[test]
+    fn actions_without_lock_or_recast_wait_for_the_clock() {
+        let mut engine = Engine::from_seed(5);
+        let spammer = monk(vec![20, 21], vec![
+            Action::new(20, Moment::new(0, 0)).with_effects(hit(20)),
+            Action::new(21, Moment::new(0, 0)).with_effects(hit(21))
+        ]);
+        let spammer_id = spammer.id;
+        engine.add_entity(spammer);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(0, 5)).ok().unwrap();
+        // One action a millisecond; the first entry in the APL is always ready again
+        assert_eq!(uses_of(&engine, &spammer_id), (0..5).map(|ms| (Moment::new(0, ms), 20)).collect::<Vec<(Moment, u32)>>());
+    }
+    #[test]
+    fn recasts_gate_the_apl() {
+        let mut engine = Engine::from_seed(5);
+        let monk = weaving_monk();
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(31, 0)).ok().unwrap();
+        let uses = uses_of(&engine, &monk_id);
+        assert_eq!(&uses[..4], &[
+            (Moment::new(0, 0), 10),
+            // The GCD is rolling, so the oGCD goes out as soon as the animation lock ends
+            (Moment::new(0, 600), 11),
+            (Moment::new(2, 500), 10),
+            (Moment::new(5, 0), 10)
+        ]);
+        assert_eq!(uses.iter().filter(|(_, action)| *action == 11).map(|(time, _)| time.clone()).collect::<Vec<Moment>>(),
+            vec![Moment::new(0, 600), Moment::new(30, 600)]);
+        assert_eq!(uses.iter().filter(|(_, action)| *action == 10).count(), 13);
+        assert!(engine.log().iter().any(|entry| entry.time == Moment::new(30, 600) && entry.event == CombatEvent::ActionReady {
+            entity: monk_id,
+            action: 11
+        }));
+    }
+    #[test]
+    fn charges_come_back_one_recast_at_a_time() {
+        let mut engine = Engine::from_seed(5);
+        // Two charges of an ability on a 30s recast, plus a second ability sharing its cooldown group
+        let monk = monk(vec![11, 12], vec![
+            Action::new(11, Moment::new(0, 0))
+                .with_cooldown_group(9)
+                .with_max_charges(2)
+                .with_recast_time(Moment::new(30, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(hit(11)),
+            Action::new(12, Moment::new(0, 0))
+                .with_cooldown_group(9)
+                .with_recast_time(Moment::new(30, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(hit(12))
+        ]);
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(61, 0)).ok().unwrap();
+        assert_eq!(uses_of(&engine, &monk_id), vec![
+            (Moment::new(0, 0), 11),
+            (Moment::new(0, 600), 11),
+            // The first charge comes back 30s after it was spent, the second one 30s after that
+            (Moment::new(30, 0), 11),
+            (Moment::new(60, 0), 11)
+        ]);
+    }
+    #[test]
+    fn weaving_past_the_gcd_is_reported_as_a_clip() {
+        let weave = |id: u32, lock: i32| Action::new(id, Moment::new(0, 0))
+            .with_recast_time(Moment::new(60, 0))
+            .with_animation_delay(Some(Moment::new(0, lock)))
+            .with_effects(hit(id));
+        let gcd = Action::new(10, Moment::new(0, 0))
+            .with_off_gcd(false)
+            .with_recast_time(Moment::new(2, 500))
+            .with_animation_delay(Some(Moment::new(0, 600)))
+            .with_effects(hit(10));
+
+        // Two weaves fit in the 1.9s window left by the GCD's lock
+        let mut engine = Engine::from_seed(5);
+        let double = monk(vec![10, 11, 12], vec![gcd.clone(), weave(11, 700), weave(12, 700)]);
+        let double_id = double.id;
+        engine.add_entity(double);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(3, 0)).ok().unwrap();
+        assert_eq!(&uses_of(&engine, &double_id)[..4], &[
+            (Moment::new(0, 0), 10),
+            (Moment::new(0, 600), 11),
+            (Moment::new(1, 300), 12),
+            (Moment::new(2, 500), 10)
+        ]);
+        assert_eq!(engine.clipped_time(&double_id), Moment::new(0, 0));
+
+        // A third one runs 0.2s past the moment the GCD comes back
+        let mut engine = Engine::from_seed(5);
+        let triple = monk(vec![10, 11, 12, 13], vec![gcd, weave(11, 700), weave(12, 700), weave(13, 700)]);
+        let triple_id = triple.id;
+        engine.add_entity(triple);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(3, 0)).ok().unwrap();
+        assert_eq!(uses_of(&engine, &triple_id)[4], (Moment::new(2, 700), 10));
+        assert_eq!(engine.clipped_time(&triple_id), Moment::new(0, 200));
+        assert!(engine.log().iter().any(|entry| entry.time == Moment::new(2, 0) && entry.event == CombatEvent::Clip {
+            entity: triple_id,
+            action: 13,
+            lost: Moment::new(0, 200)
+        }));
+    }
+    #[test]
+    fn clips_follow_the_shared_gcd_timer_and_usable_actions() {
+        let gcd = |id: u32| Action::new(id, Moment::new(0, 0))
+            .with_off_gcd(false)
+            .with_recast_time(Moment::new(2, 500))
+            .with_animation_delay(Some(Moment::new(0, 600)))
+            .with_effects(hit(id));
+        let weave = Action::new(11, Moment::new(0, 0))
+            .with_recast_time(Moment::new(60, 0))
+            .with_animation_delay(Some(Moment::new(0, 700)))
+            .with_effects(hit(11));
+        // A GCD action on its own timer that is never used must not stand in for the global cooldown
+        let grouped = gcd(12).with_cooldown_group(6).with_recast_time(Moment::new(30, 0));
+
+        let mut engine = Engine::from_seed(5);
+        let entity = monk(vec![10, 11], vec![grouped.clone(), gcd(10), weave.clone()]);
+        let id = entity.id;
+        engine.add_entity(entity);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(3, 0)).ok().unwrap();
+        assert_eq!(&uses_of(&engine, &id)[..2], &[(Moment::new(0, 0), 10), (Moment::new(0, 600), 11)]);
+        assert_eq!(engine.clipped_time(&id), Moment::new(0, 0));
+
+        // Nothing is lost by weaving while no GCD action could have been used anyway
+        let mut engine = Engine::from_seed(5);
+        let entity = monk(vec![11], vec![grouped, gcd(10).with_available_condition(|_| false), weave]);
+        let id = entity.id;
+        engine.add_entity(entity);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(3, 0)).ok().unwrap();
+        assert_eq!(uses_of(&engine, &id), vec![(Moment::new(0, 0), 11)]);
+        assert_eq!(engine.clipped_time(&id), Moment::new(0, 0));
+    }
+    #[test]
+    fn speed_and_haste_shorten_casts_and_the_gcd() {
+        let mut engine = Engine::from_seed(5);
+        let mut red_mage = nuking_red_mage();
+        // 38 thousandths over the base value: 2.5s becomes 2.405s, kept to the hundredth
+        red_mage.set_statistic("Spell Speed", 1000);
+        let red_mage_id = red_mage.id;
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(3, 0)).ok().unwrap();
+        assert!(engine.log().iter().any(|entry| entry.time == Moment::new(2, 400) && match entry.event {
+            CombatEvent::CastFinish { source, .. } => source == red_mage_id,
+            _ => false
+        }));
+
+        // A GCD weaponskill under a 10% haste buff comes back after 2.25s
+        let mut engine = Engine::from_seed(5);
+        let mut monk = monk(vec![10], vec![
+            Action::new(10, Moment::new(0, 0))
+                .with_off_gcd(false)
+                .with_recast_time(Moment::new(2, 500))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(|source, _| vec![Effect::ApplyAura {
+                    source: source.clone(),
+                    target: source.clone(),
+                    aura: 100,
+                    duration: Moment::new(30, 0),
+                    effects: vec![AuraEffect::Haste { modifier: 10 }]
+                }])
+        ]);
+        monk.set_statistic("Skill Speed", 364);
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(5, 0)).ok().unwrap();
+        let uses:Vec<Moment> = uses_of(&engine, &monk_id).into_iter().map(|(time, _)| time).collect();
+        // The first use goes out before the buff is up
+        assert_eq!(uses, vec![Moment::new(0, 0), Moment::new(2, 500), Moment::new(4, 750)]);
+    }
+    fn hostile_dummy() -> Entity {
+        let mut big_bad = Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![]));
+        big_bad.hostile = true;
+        big_bad
+    }
+    fn autos_of(engine: &Engine, entity: &Uuid) -> Vec<Moment> {
+        engine.log().iter().filter_map(|entry| match entry.event {
+            CombatEvent::Damage { ref source, action: AUTO_ATTACK, .. } if source == entity => Some(entry.time.clone()),
+            _ => None
+        }).collect()
+    }
+    #[test]
+    fn auto_attacks_swing_on_their_own_timer() {
+        let swinging_monk = || {
+            let mut monk = monk(vec![], vec![]);
+            monk.set_statistic("Auto-attack", 105);
+            monk.auto_attack = Some(AutoAttack {
+                delay: Moment::new(3, 0),
+                potency: 110,
+                r#type: DamageType::Blunt
+            });
+            monk
+        };
+        let mut engine = Engine::from_seed(5);
+        let monk = swinging_monk();
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(hostile_dummy());
+        engine.run_until(Moment::new(10, 0)).ok().unwrap();
+        assert_eq!(autos_of(&engine, &monk_id), vec![Moment::new(0, 0), Moment::new(3, 0), Moment::new(6, 0), Moment::new(9, 0)]);
+        assert!(engine.damage.total_by_source(&monk_id) > 0);
+        assert_eq!(engine.entities[&monk_id].last_auto, Moment::new(9, 0));
+
+        // Haste shortens the delay
+        let mut engine = Engine::from_seed(5);
+        let mut monk = swinging_monk();
+        monk.add_aura(Aura {
+            id: 100,
+            source: monk.clone(),
+            target: monk.clone(),
+            start_time: Moment::new(0, 0),
+            end_time: Moment::new(60, 0),
+            effects: vec![AuraEffect::Haste { modifier: 20 }],
+            stacks: 1
+        }, &AuraDefinition::new(100));
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(hostile_dummy());
+        engine.run_until(Moment::new(7, 0)).ok().unwrap();
+        assert_eq!(autos_of(&engine, &monk_id), vec![Moment::new(0, 0), Moment::new(2, 400), Moment::new(4, 800)]);
+
+        // A target that shows up after the pull gets swung at straight away
+        let mut engine = Engine::from_seed(5);
+        let monk = swinging_monk();
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.run_until(Moment::new(4, 0)).ok().unwrap();
+        engine.add_entity(hostile_dummy());
+        engine.run_until(Moment::new(10, 0)).ok().unwrap();
+        assert_eq!(autos_of(&engine, &monk_id), vec![Moment::new(4, 0), Moment::new(7, 0)]);
+
+        // Once the only opponent is dead, the next swing finds nobody and nothing is left to happen
+        let mut engine = Engine::from_seed(5);
+        let monk = swinging_monk();
+        let monk_id = monk.id;
+        let mut big_bad = hostile_dummy();
+        big_bad.set_max_hp(1);
+        engine.add_entity(monk);
+        engine.add_entity(big_bad);
+        assert_eq!(engine.run().ok(), Some(Moment::new(3, 0)));
+        assert_eq!(autos_of(&engine, &monk_id), vec![Moment::new(0, 0)]);
+    }
+    #[test]
+    fn casts_hold_auto_attacks_back() {
+        let mut engine = Engine::from_seed(5);
+        let mut red_mage = nuking_red_mage();
+        red_mage.auto_attack = Some(AutoAttack {
+            delay: Moment::new(2, 0),
+            potency: 110,
+            r#type: DamageType::Piercing
+        });
+        let red_mage_id = red_mage.id;
+        engine.add_entity(red_mage);
+        engine.add_entity(hostile_dummy());
+        engine.run_until(Moment::new(7, 0)).ok().unwrap();
+        // Casting from 0 to 2.5 and from 3.25 to 5.75; the swings due at 2.0 and 4.5 wait for the casts to end
+        assert_eq!(autos_of(&engine, &red_mage_id), vec![Moment::new(0, 0), Moment::new(2, 500), Moment::new(5, 750)]);
+    }
+    fn dotting_monk(apl: Vec<u32>) -> Entity {
+        monk(apl, vec![
+            // Puts a 30s DoT up on the target
+            Action::new(20, Moment::new(0, 0))
+                .with_recast_time(Moment::new(60, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(|source, targets| targets.into_iter().map(|target| Effect::ApplyAura {
+                    source: source.clone(),
+                    target: target.clone(),
+                    aura: 1000,
+                    duration: Moment::new(30, 0),
+                    effects: vec![]
+                }).collect()),
+            // The same DoT behind a 2.5s cast
+            Action::new(22, Moment::new(2, 500))
+                .with_recast_time(Moment::new(60, 0))
+                .with_effects(|source, targets| targets.into_iter().map(|target| Effect::ApplyAura {
+                    source: source.clone(),
+                    target: target.clone(),
+                    aura: 1000,
+                    duration: Moment::new(30, 0),
+                    effects: vec![]
+                }).collect()),
+            // A 2s damage buff on the monk
+            Action::new(21, Moment::new(0, 0))
+                .with_recast_time(Moment::new(60, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(|source, _| vec![Effect::ApplyAura {
+                    source: source.clone(),
+                    target: source.clone(),
+                    aura: 1001,
+                    duration: Moment::new(2, 0),
+                    effects: vec![]
+                }])
+        ])
+    }
+    fn dot_catalogue() -> AuraCatalogue {
+        AuraCatalogue::new()
+            .with_aura(1000, vec![AuraEffect::DoT {
+                potency: 50,
+                skill_type: SkillType::Skill,
+                r#type: DamageType::Blunt
+            }])
+            .with_aura(1001, vec![AuraEffect::DamageModifier {
+                damage_type: vec![],
+                skill_type: vec![],
+                modifier: 50
+            }])
+    }
+    fn ticks_of(engine: &Engine, entity: &Uuid) -> Vec<(Moment, u32)> {
+        engine.log().iter().filter_map(|entry| match entry.event {
+            CombatEvent::Damage { ref source, amount, periodic: true, .. } if source == entity => Some((entry.time.clone(), amount)),
+            _ => None
+        }).collect()
+    }
+    #[test]
+    fn dots_tick_on_the_server_tick() {
+        let mut engine = Engine::from_seed(5)
+            .with_aura_catalogue(dot_catalogue())
+            .with_damage_strategy(Box::new(ExpectedDamageStrategy::new()));
+        let phase = engine.server_tick_phase();
+        assert!(phase >= Moment::new(0, 0) && phase < Moment::new(3, 0));
+        let monk = dotting_monk(vec![20]);
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(40, 0)).ok().unwrap();
+        // Applied at 0, so the aura gets every tick in (0, 30]
+        let first = match phase > Moment::new(0, 0) {
+            true => phase.clone(),
+            false => Moment::new(3, 0)
+        };
+        let ticks = ticks_of(&engine, &monk_id);
+        assert_eq!(ticks.iter().map(|(time, _)| time.clone()).collect::<Vec<Moment>>(),
+            (0..10).map(|i| first.clone() + Moment::new(3, 0) * i).collect::<Vec<Moment>>());
+        assert!(ticks.iter().all(|(_, amount)| *amount > 0));
+        // Nothing ticks once the aura is gone
+        engine.run_until(Moment::new(59, 0)).ok().unwrap();
+        assert_eq!(ticks_of(&engine, &monk_id).len(), 10);
+
+        // The same seed puts the tick at the same phase
+        assert_eq!(Engine::from_seed(5).server_tick_phase(), phase);
+    }
+    #[test]
+    fn dots_snapshot_when_applied() {
+        let tick_amounts = |apl: Vec<u32>| {
+            let mut engine = Engine::from_seed(5)
+                .with_aura_catalogue(dot_catalogue())
+                .with_damage_strategy(Box::new(ExpectedDamageStrategy::new()));
+            let monk = dotting_monk(apl);
+            let monk_id = monk.id;
+            engine.add_entity(monk);
+            engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+            engine.run_until(Moment::new(40, 0)).ok().unwrap();
+            ticks_of(&engine, &monk_id)
+        };
+        let plain = tick_amounts(vec![20]);
+        // The buff goes up at 0 and the DoT at 0.6; the buff is long gone by the last tick, but the DoT kept it
+        let buffed = tick_amounts(vec![21, 20]);
+        assert_eq!(buffed.len(), 10);
+        let (last_time, last_amount) = buffed.last().cloned().unwrap();
+        assert!(last_time > Moment::new(2, 0));
+        assert!(f64::from(last_amount) > f64::from(plain[0].1) * 1.45);
+        // A hard cast snapshots when it goes off at 3.1, after the buff fell off at 2
+        let cast = tick_amounts(vec![21, 22]);
+        assert!(!cast.is_empty());
+        assert!(cast.iter().all(|(_, amount)| *amount == plain[0].1));
+    }
+    #[test]
+    fn stacks_gate_and_are_spent_by_actions() {
+        let catalogue = AuraCatalogue::new()
+            .with_definition(AuraDefinition::new(1003).with_initial_stacks(3).with_max_stacks(3));
+        let mut engine = Engine::from_seed(5).with_aura_catalogue(catalogue);
+        let monk = monk(vec![31, 30], vec![
+            // Grants three stacks
+            Action::new(30, Moment::new(0, 0))
+                .with_recast_time(Moment::new(60, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_effects(|source, _| vec![Effect::ApplyAura {
+                    source: source.clone(),
+                    target: source.clone(),
+                    aura: 1003,
+                    duration: Moment::new(30, 0),
+                    effects: vec![]
+                }]),
+            // Spends one per use
+            Action::new(31, Moment::new(0, 0))
+                .with_recast_time(Moment::new(1, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_required_stacks(1003, 1)
+                .with_effects(|source, _| vec![Effect::ConsumeStacks {
+                    source: source.clone(),
+                    target: source.clone(),
+                    aura: 1003,
+                    stacks: 1
+                }])
+        ]);
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(10, 0)).ok().unwrap();
+        assert_eq!(uses_of(&engine, &monk_id), vec![
+            (Moment::new(0, 0), 30),
+            (Moment::new(0, 600), 31),
+            (Moment::new(1, 600), 31),
+            (Moment::new(2, 600), 31)
+        ]);
+        let stacks:Vec<u32> = engine.log().iter().filter_map(|entry| match entry.event {
+            CombatEvent::AuraStacks { stacks, .. } => Some(stacks),
+            _ => None
+        }).collect();
+        assert_eq!(stacks, vec![2, 1]);
+        assert!(engine.log().iter().any(|entry| entry.time == Moment::new(2, 600) && entry.event == CombatEvent::AuraRemoved {
+            source: monk_id,
+            target: monk_id,
+            aura: 1003
+        }));
+        assert_eq!(engine.entities[&monk_id].aura_stacks(&1003, None), 0);
+    }
+    #[test]
+    fn expiring_auras_are_logged_and_acted_on() {
+        let mut engine = Engine::from_seed(5);
+        // Only worth pressing while its own buff is down
+        let mut monk = monk(vec![40], vec![
+            Action::new(40, Moment::new(0, 0))
+                .with_recast_time(Moment::new(1, 0))
+                .with_animation_delay(Some(Moment::new(0, 600)))
+                .with_available_condition(|entity| entity.aura_stacks(&1004, Some(entity.id)) == 0)
+                .with_effects(|source, _| vec![Effect::ApplyAura {
+                    source: source.clone(),
+                    target: source.clone(),
+                    aura: 1004,
+                    duration: Moment::new(5, 0),
+                    effects: vec![]
+                }])
+        ]);
+        // Put up before the monk joins the fight
+        monk.add_aura(Aura {
+            id: 100,
+            source: monk.clone(),
+            target: monk.clone(),
+            start_time: Moment::new(0, 0),
+            end_time: Moment::new(3, 0),
+            effects: vec![AuraEffect::Haste { modifier: 20 }],
+            stacks: 1
+        }, &AuraDefinition::new(100));
+        let monk_id = monk.id;
+        engine.add_entity(monk);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(12, 0)).ok().unwrap();
+        assert_eq!(uses_of(&engine, &monk_id), vec![(Moment::new(0, 0), 40), (Moment::new(5, 0), 40), (Moment::new(10, 0), 40)]);
+        let expired:Vec<(Moment, u32)> = engine.log().iter().filter_map(|entry| match entry.event {
+            CombatEvent::AuraExpired { aura, .. } => Some((entry.time.clone(), aura)),
+            _ => None
+        }).collect();
+        assert_eq!(expired, vec![(Moment::new(3, 0), 100), (Moment::new(5, 0), 1004), (Moment::new(10, 0), 1004)]);
+        assert_eq!(engine.entities[&monk_id].haste(), 0);
+    }
+    #[test]
+    fn casts_spend_mana_and_wait_for_it_to_come_back() {
+        let mana_red_mage = |regen: i32| {
+            let mut red_mage = monk(vec![1], vec![
+                Action::new(1, Moment::new(2, 500))
+                    .with_off_gcd(false)
+                    .with_recast_time(Moment::new(2, 500))
+                    .with_cost("MP", 400)
+                    .with_effects(hit(1))
+            ]);
+            red_mage.add_resource("MP", 1000, 1000);
+            red_mage.set_resource_regen("MP", regen);
+            red_mage
+        };
+        let mut engine = Engine::from_seed(5);
+        let red_mage = mana_red_mage(0);
+        let red_mage_id = red_mage.id;
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(20, 0)).ok().unwrap();
+        // Paid as each cast goes off; 200 left is not enough for a third
+        assert_eq!(uses_of(&engine, &red_mage_id), vec![(Moment::new(2, 500), 1), (Moment::new(5, 0), 1)]);
+        assert_eq!(engine.entities[&red_mage_id].resource("MP"), Some(200));
+        // Nothing regenerates, so the fight can run out
+        assert!(engine.run().is_ok());
+
+        let mut engine = Engine::from_seed(5);
+        let phase = engine.server_tick_phase();
+        let red_mage = mana_red_mage(200);
+        let red_mage_id = red_mage.id;
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+        engine.run_until(Moment::new(30, 0)).ok().unwrap();
+        let regen:Vec<(Moment, i32)> = engine.log().iter().filter_map(|entry| match entry.event {
+            CombatEvent::ResourceChange { ref entity, amount, .. } if entity == &red_mage_id && amount > 0 => Some((entry.time.clone(), amount)),
+            _ => None
+        }).collect();
+        assert!(!regen.is_empty());
+        assert!(regen.iter().all(|(time, amount)| (time.as_millis() - phase.as_millis()) % 3000 == 0 && *amount == 200));
+        assert!(uses_of(&engine, &red_mage_id).len() > 2);
+        assert!(engine.entities[&red_mage_id].resource("MP").unwrap() <= 1000);
+
+        // The dead stop regenerating, so the ticks stop and the fight can run out
+        let red_mage = engine.entities.get_mut(&red_mage_id).unwrap();
+        red_mage.set_max_hp(100);
+        red_mage.take_damage(100);
+        let mp = red_mage.resource("MP");
+        assert!(engine.run().is_ok());
+        assert_eq!(engine.entities[&red_mage_id].resource("MP"), mp);
+    }
+    #[test]
+    fn expected_damage_removes_seed_noise() {
+        let total = |seed: u64| {
+            let mut engine = Engine::from_seed(seed).with_damage_strategy(Box::new(ExpectedDamageStrategy::new()));
+            let red_mage = nuking_red_mage();
+            let red_mage_id = red_mage.id;
+            engine.add_entity(red_mage);
+            engine.add_entity(Entity::create("big_bad".to_string(), None, 70, Vec::new(), Arc::new(vec![])));
+            engine.run_until(Moment::new(30, 0)).ok().unwrap();
+            engine.damage.total_by_source(&red_mage_id)
+        };
+        assert!(total(1) > 0);
+        assert_eq!(total(1), total(2));
+    }
     #[test]
     fn handle_aura_cast_time_interactions() {
         
@@ -0,0 +1,127 @@
+use simxiv_prelude::{Moment, DefenseRoll};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CombatEvent {
+    CastStart {
+        source: Uuid,
+        target: Uuid,
+        action: u32,
+        duration: Moment
+    },
+    CastFinish {
+        source: Uuid,
+        target: Uuid,
+        action: u32
+    },
+    ActionReady {
+        entity: Uuid,
+        action: u32
+    },
+    AnimationLock {
+        entity: Uuid,
+        action: u32,
+        duration: Moment
+    },
+    Idle {
+        entity: Uuid
+    },
+    // An off-GCD action held the entity past the moment its GCD came back
+    Clip {
+        entity: Uuid,
+        action: u32,
+        lost: Moment
+    },
+    AuraApplied {
+        source: Uuid,
+        target: Uuid,
+        aura: u32,
+        duration: Moment
+    },
+    AuraRemoved {
+        source: Uuid,
+        target: Uuid,
+        aura: u32
+    },
+    // The aura ran out
+    AuraExpired {
+        source: Uuid,
+        target: Uuid,
+        aura: u32
+    },
+    // The aura's stack count changed, to this many
+    AuraStacks {
+        source: Uuid,
+        target: Uuid,
+        aura: u32,
+        stacks: u32
+    },
+    Damage {
+        source: Uuid,
+        target: Uuid,
+        action: u32,
+        amount: u32,
+        critical: bool,
+        direct: bool,
+        defense: DefenseRoll,
+        periodic: bool
+    },
+    Heal {
+        source: Uuid,
+        target: Uuid,
+        amount: u32
+    },
+    ResourceChange {
+        entity: Uuid,
+        resource: String,
+        amount: i32
+    },
+    Death {
+        entity: Uuid
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub time: Moment,
+    pub event: CombatEvent
+}
+
+pub type Subscriber = Box<dyn FnMut(&LogEntry)>;
+
+// Keeps every entry of the fight and hands each one to the subscribers as it happens
+pub struct CombatLog {
+    entries: Vec<LogEntry>,
+    subscribers: Vec<Subscriber>
+}
+
+impl CombatLog {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            subscribers: vec![]
+        }
+    }
+    pub fn subscribe(&mut self, subscriber: Subscriber) {
+        self.subscribers.push(subscriber);
+    }
+    pub fn push(&mut self, time: Moment, event: CombatEvent) {
+        let entry = LogEntry {
+            time,
+            event
+        };
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber(&entry);
+        }
+        self.entries.push(entry);
+    }
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+}
+
+impl Default for CombatLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,74 @@
+use simxiv_prelude::{Moment, AppliedDamage};
+use uuid::Uuid;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DamageKey {
+    pub source: Uuid,
+    pub target: Uuid,
+    pub action: u32
+}
+
+#[derive(Clone)]
+pub struct DamageRecord {
+    pub time: Moment,
+    pub damage: AppliedDamage
+}
+
+// Every hit the engine resolved, keyed by who dealt it, who took it and what it came from
+pub struct DamageMeter {
+    records: HashMap<DamageKey, Vec<DamageRecord>>
+}
+
+impl DamageMeter {
+    pub fn new() -> Self {
+        Self {
+            records: HashMap::new()
+        }
+    }
+    pub fn record(&mut self, time: Moment, source: Uuid, target: Uuid, action: u32, damage: AppliedDamage) {
+        self.records.entry(DamageKey {
+            source,
+            target,
+            action
+        }).or_default().push(DamageRecord {
+            time,
+            damage
+        });
+    }
+    pub fn hits(&self, source: &Uuid, target: &Uuid, action: u32) -> &[DamageRecord] {
+        self.records.get(&DamageKey {
+            source: *source,
+            target: *target,
+            action
+        }).map(|records| records.as_slice()).unwrap_or(&[])
+    }
+    pub fn records(&self) -> impl Iterator<Item = (&DamageKey, &DamageRecord)> {
+        self.records.iter().flat_map(|(key, records)| records.iter().map(move |record| (key, record)))
+    }
+    fn total_where(&self, filter: impl Fn(&DamageKey) -> bool) -> u64 {
+        self.records.iter()
+            .filter(|(key, _)| filter(key))
+            .flat_map(|(_, records)| records.iter())
+            .map(|record| u64::from(record.damage.value))
+            .sum()
+    }
+    pub fn total_by_source(&self, source: &Uuid) -> u64 {
+        self.total_where(|key| key.source == *source)
+    }
+    pub fn total_on_target(&self, target: &Uuid) -> u64 {
+        self.total_where(|key| key.target == *target)
+    }
+    pub fn total_by_action(&self, source: &Uuid, action: u32) -> u64 {
+        self.total_where(|key| key.source == *source && key.action == action)
+    }
+    pub fn total(&self) -> u64 {
+        self.total_where(|_| true)
+    }
+}
+
+impl Default for DamageMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
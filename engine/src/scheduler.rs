@@ -0,0 +1,130 @@
+use simxiv_prelude::Moment;
+use uuid::Uuid;
+use std::collections::BinaryHeap;
+use std::cmp::{Ordering, Reverse};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    // The entity's status is due to change: a cast finishes, an animation lock ends or it starts idling
+    Wake {
+        entity: Uuid
+    },
+    // An action's recast is over
+    ActionReady {
+        entity: Uuid,
+        action: u32
+    },
+    AutoAttack {
+        entity: Uuid
+    },
+    AuraExpiry {
+        target: Uuid,
+        aura: u32
+    },
+    // The global 3-second tick that periodic effects land on
+    ServerTick
+}
+
+struct Scheduled {
+    time: Moment,
+    sequence: u64,
+    event: Event
+}
+impl PartialEq for Scheduled {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cmp(rhs) == Ordering::Equal
+    }
+}
+impl Eq for Scheduled {}
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+impl Ord for Scheduled {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        // Events at the same moment are handed out in the order they were posted, except for the server tick which
+        // resolves first, so that an aura falling off on a tick still gets it and one going up on a tick does not
+        let tick_first = |scheduled: &Scheduled| scheduled.event != Event::ServerTick;
+        self.time.cmp(&rhs.time)
+            .then(tick_first(self).cmp(&tick_first(rhs)))
+            .then(self.sequence.cmp(&rhs.sequence))
+    }
+}
+
+pub struct Scheduler {
+    queue: BinaryHeap<Reverse<Scheduled>>,
+    sequence: u64
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            sequence: 0
+        }
+    }
+    pub fn schedule(&mut self, time: Moment, event: Event) {
+        self.queue.push(Reverse(Scheduled {
+            time,
+            sequence: self.sequence,
+            event
+        }));
+        self.sequence += 1;
+    }
+    pub fn next_time(&self) -> Option<Moment> {
+        self.queue.peek().map(|Reverse(scheduled)| scheduled.time.clone())
+    }
+    // Pops the next event if it is due at or before the given moment
+    pub fn pop_until(&mut self, time: &Moment) -> Option<(Moment, Event)> {
+        match self.next_time() {
+            Some(ref next) if next <= time => self.queue.pop().map(|Reverse(scheduled)| (scheduled.time, scheduled.event)),
+            _ => None
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scheduler, Event};
+    use simxiv_prelude::Moment;
+    use uuid::Uuid;
+
+    #[test]
+    fn pops_in_time_then_posting_order() {
+        let mut scheduler = Scheduler::new();
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        scheduler.schedule(Moment::new(2, 0), Event::Wake { entity: first });
+        scheduler.schedule(Moment::new(1, 500), Event::Wake { entity: second });
+        scheduler.schedule(Moment::new(1, 500), Event::Wake { entity: first });
+        assert_eq!(scheduler.next_time(), Some(Moment::new(1, 500)));
+        assert_eq!(scheduler.pop_until(&Moment::new(1, 0)), None);
+        assert_eq!(scheduler.pop_until(&Moment::new(1, 500)), Some((Moment::new(1, 500), Event::Wake { entity: second })));
+        assert_eq!(scheduler.pop_until(&Moment::new(1, 500)), Some((Moment::new(1, 500), Event::Wake { entity: first })));
+        assert_eq!(scheduler.pop_until(&Moment::new(1, 500)), None);
+        assert_eq!(scheduler.len(), 1);
+    }
+
+    #[test]
+    fn server_ticks_go_first() {
+        let mut scheduler = Scheduler::new();
+        let entity = Uuid::new_v4();
+        scheduler.schedule(Moment::new(3, 0), Event::Wake { entity });
+        scheduler.schedule(Moment::new(3, 0), Event::ServerTick);
+        assert_eq!(scheduler.pop_until(&Moment::new(3, 0)), Some((Moment::new(3, 0), Event::ServerTick)));
+        assert_eq!(scheduler.pop_until(&Moment::new(3, 0)), Some((Moment::new(3, 0), Event::Wake { entity })));
+    }
+}
@@ -0,0 +1,183 @@
+use simxiv_prelude::{Job, SimError, is_caster};
+use crate::{Engine, BatchRunner};
+use std::collections::HashMap;
+
+// The statistics that feed the damage formula, for a job's role
+fn stats_for(job: &Option<Job>) -> (String, Vec<String>) {
+    let (main, weapon, speed) = match job.as_ref().is_some_and(is_caster) {
+        true => ("Magic Attack Power", "Magic Damage", "Spell Speed"),
+        false => ("Attack Power", "Physical Damage", "Skill Speed")
+    };
+    (main.to_string(), vec![main, weapon, "Critical Hit Rate", "Direct Hit Rate", "Determination", speed, "Tenacity"]
+        .into_iter().map(|stat| stat.to_string()).collect())
+}
+
+#[derive(Clone, Debug)]
+pub struct StatWeights {
+    pub baseline: f64,
+    pub delta: u32,
+    pub main_stat: String,
+    pub per_point: HashMap<String, f64>
+}
+
+impl StatWeights {
+    // DPS gained per point of the statistic
+    pub fn per_point(&self, stat: &str) -> Option<f64> {
+        self.per_point.get(stat).cloned()
+    }
+    // The same, in points of main stat
+    pub fn normalised(&self, stat: &str) -> Option<f64> {
+        match (self.per_point(stat), self.per_point(&self.main_stat)) {
+            (Some(weight), Some(main)) if main != 0.0 => Some(weight / main),
+            _ => None
+        }
+    }
+}
+
+// Re-runs the encounter once per statistic with that statistic raised by `delta` on the named entity.
+// Every variant goes over the same seeds, so that the comparison is not drowned in roll noise; with an
+// ExpectedDamageStrategy, a single run per variant is enough.
+pub struct StatWeightCalculator<F> where F: Fn(u64) -> Engine + Sync {
+    setup: F,
+    entity: String,
+    stats: Option<Vec<String>>,
+    delta: u32,
+    runs: usize,
+    first_seed: u64
+}
+
+impl<F> StatWeightCalculator<F> where F: Fn(u64) -> Engine + Sync {
+    pub fn new(entity: &str, setup: F) -> Self {
+        Self {
+            setup,
+            entity: entity.to_string(),
+            stats: None,
+            delta: 10,
+            runs: 1,
+            first_seed: 0
+        }
+    }
+    pub fn with_delta(self, delta: u32) -> Self {
+        Self {
+            delta: delta.max(1),
+            ..self
+        }
+    }
+    pub fn with_runs(self, runs: usize) -> Self {
+        Self {
+            runs: runs.max(1),
+            ..self
+        }
+    }
+    pub fn with_first_seed(self, first_seed: u64) -> Self {
+        Self {
+            first_seed,
+            ..self
+        }
+    }
+    // Replaces the default statistics of the entity's role
+    pub fn with_stats(self, stats: Vec<&str>) -> Self {
+        Self {
+            stats: Some(stats.into_iter().map(|stat| stat.to_string()).collect()),
+            ..self
+        }
+    }
+    fn mean_dps(&self, raised: Option<&str>) -> Result<f64, SimError> {
+        let report = BatchRunner::new(self.runs, |seed| {
+            let mut engine = (self.setup)(seed);
+            if let Some(stat) = raised {
+                for entity in engine.entities.values_mut().filter(|entity| entity.name == self.entity) {
+                    let value = entity.get_statistic(stat);
+                    entity.set_statistic(stat, value + self.delta);
+                }
+            }
+            engine
+        }).with_first_seed(self.first_seed).run()?;
+        report.dps_of(&self.entity).map(|dps| dps.mean()).ok_or(SimError::Unknown)
+    }
+    pub fn run(&self) -> Result<StatWeights, SimError> {
+        let job = (self.setup)(self.first_seed).entities.values()
+            .find(|entity| entity.name == self.entity)
+            .map(|entity| entity.job.clone())
+            .ok_or(SimError::Unknown)?;
+        let (main_stat, defaults) = stats_for(&job);
+        let stats = self.stats.clone().unwrap_or(defaults);
+        let baseline = self.mean_dps(None)?;
+        let mut per_point = HashMap::new();
+        for stat in stats.iter().chain(Some(&main_stat)) {
+            if !per_point.contains_key(stat) {
+                per_point.insert(stat.clone(), (self.mean_dps(Some(stat))? - baseline) / self.delta as f64);
+            }
+        }
+        Ok(StatWeights {
+            baseline,
+            delta: self.delta,
+            main_stat,
+            per_point
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatWeightCalculator;
+    use crate::{Engine, EndCondition};
+    use simxiv_prelude::{ConditionalAction, Job, Entity, Action, Effect, Moment, SkillType, DamageType, Element, ExpectedDamageStrategy};
+    use std::sync::Arc;
+
+    fn encounter(seed: u64) -> Engine {
+        let mut engine = Engine::from_seed(seed)
+            .with_damage_strategy(Box::new(ExpectedDamageStrategy::new()))
+            .with_end_condition(EndCondition::TimeLimit(Moment::new(60, 0)));
+        let mut red_mage = Entity::create("red_mage".to_string(), Some(Job::RDM), 70, vec![
+            ConditionalAction::Cast {
+                spell: 1,
+                selector: Arc::new(Box::new(|_, targets| {
+                    targets.into_iter().find(|target| target.name == "big_bad").map(|target| target.id)
+                }))
+            }
+        ], Arc::new(vec![
+            Action::new(1, Moment::new(2, 500))
+                .with_off_gcd(false)
+                .with_recast_time(Moment::new(2, 500))
+                .with_effects(|source, targets| targets.into_iter().map(|target| Effect::Damage {
+                    source: source.clone(),
+                    target: target.clone(),
+                    action: 1,
+                    potency: 300,
+                    skill_type: SkillType::Spell,
+                    r#type: DamageType::Magic(Element::Unaspected),
+                    periodic: false
+                }).collect())
+        ]));
+        red_mage.set_statistic("Magic Attack Power", 2011);
+        red_mage.set_statistic("Magic Damage", 105);
+        red_mage.set_statistic("Critical Hit Rate", 1155);
+        red_mage.set_statistic("Direct Hit Rate", 923);
+        red_mage.set_statistic("Determination", 1834);
+        red_mage.set_statistic("Spell Speed", 600);
+        red_mage.set_statistic("Tenacity", 364);
+        engine.add_entity(red_mage);
+        engine.add_entity(Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![])));
+        engine
+    }
+
+    #[test]
+    fn weighs_every_stat_against_the_main_stat() {
+        let weights = StatWeightCalculator::new("red_mage", encounter).with_delta(100).run().ok().unwrap();
+        assert!(weights.baseline > 0.0);
+        assert_eq!(weights.main_stat, "Magic Attack Power");
+        assert_eq!(weights.normalised("Magic Attack Power"), Some(1.0));
+        assert!(weights.per_point("Magic Attack Power").unwrap() > 0.0);
+        // A point of weapon damage is worth far more than a point of anything else
+        assert!(weights.normalised("Magic Damage").unwrap() > 1.0);
+        for stat in ["Critical Hit Rate", "Direct Hit Rate", "Determination"].iter() {
+            let weight = weights.normalised(stat).unwrap();
+            assert!(weight > 0.0 && weight < 1.0, "{} weighs {}", stat, weight);
+        }
+        // Speed only pays off once it fits in another cast before the time limit
+        assert!(weights.normalised("Spell Speed").unwrap() >= 0.0);
+        assert!(weights.per_point("Skill Speed").is_none());
+        assert!(StatWeightCalculator::new("nobody", encounter).run().is_err());
+    }
+}
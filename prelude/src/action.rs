@@ -24,6 +24,9 @@ pub enum ActionTarget {
     }
 }
 
+// The cooldown group every weaponskill and spell on the global cooldown belongs to
+pub const GCD_COOLDOWN_GROUP: u32 = 58;
+
 #[derive(Clone)]
 pub struct Action {
     pub id: u32,
@@ -34,6 +37,11 @@ pub struct Action {
     pub off_gcd: bool,
     pub cast_time: Arc<Box<Fn(&Entity) -> Moment>>, // None = off-gcd
     pub recast_time: Moment,
+    // Actions in the same cooldown group share one recast timer
+    pub cooldown_group: Option<u32>,
+    pub max_charges: u32,
+    // Resources spent when the action goes off; it cannot be started without them
+    pub costs: Vec<(String, u32)>
 }
 
 impl Action {
@@ -49,7 +57,10 @@ impl Action {
             effect: Arc::new(Box::new(|_, _| vec![])),
             animation_delay: None,
             cast_time: Arc::new(Box::new(move |_| base_cast_time.clone())),
-            recast_time: Moment::new(0, 0)
+            recast_time: Moment::new(0, 0),
+            cooldown_group: None,
+            max_charges: 1,
+            costs: vec![]
         }
     }
     pub fn with_target_type(self, new_type: ActionTarget) -> Self {
@@ -61,7 +72,10 @@ impl Action {
             off_gcd: self.off_gcd,
             animation_delay: self.animation_delay,
             cast_time: self.cast_time,
-            recast_time: self.recast_time
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
         }
     }
     pub fn with_available_condition(self, new_avail: impl Fn(&Entity) -> bool + 'static) -> Self {
@@ -73,9 +87,17 @@ impl Action {
             off_gcd: self.off_gcd,
             animation_delay: self.animation_delay,
             cast_time: self.cast_time,
-            recast_time: self.recast_time
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
         }
     }
+    // Only available while the entity has at least this many stacks of its own aura, on top of any other condition
+    pub fn with_required_stacks(self, aura: u32, stacks: u32) -> Self {
+        let available = Arc::clone(&self.available);
+        self.with_available_condition(move |entity| available(entity) && entity.aura_stacks(&aura, Some(entity.id)) >= stacks)
+    }
     pub fn with_effects(self, new_effect: impl Fn(&Entity, Vec<&Entity>) -> Vec<Effect> + 'static) -> Self {
         Self {
             id: self.id,
@@ -85,7 +107,10 @@ impl Action {
             animation_delay: self.animation_delay,
             cast_time: self.cast_time,
             off_gcd: self.off_gcd,
-            recast_time: self.recast_time
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
         }
     }
     pub fn with_effect_modifier(self, new_effect: impl Fn(&Entity, Vec<&Entity>) -> Vec<Effect> + 'static) -> Self {
@@ -103,7 +128,10 @@ impl Action {
             animation_delay: self.animation_delay,
             cast_time: self.cast_time,
             off_gcd: self.off_gcd,
-            recast_time: self.recast_time
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
         }
     }
     pub fn with_animation_delay(self, new_delay: Option<Moment>) -> Self {
@@ -115,7 +143,10 @@ impl Action {
             animation_delay: new_delay,
             cast_time: self.cast_time,
             off_gcd: self.off_gcd,
-            recast_time: self.recast_time
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
         }
     }
     pub fn with_cast_modifier(self, modifier: impl Fn(&Entity, Moment) -> Moment + 'static) -> Self {
@@ -130,7 +161,25 @@ impl Action {
                 modifier(entity, (old_cast)(entity))
             })),
             off_gcd: self.off_gcd,
-            recast_time: self.recast_time
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
+        }
+    }
+    pub fn with_off_gcd(self, off_gcd: bool) -> Self {
+        Self {
+            id: self.id,
+            target_type: self.target_type,
+            available: self.available,
+            effect: self.effect,
+            off_gcd,
+            animation_delay: self.animation_delay,
+            cast_time: self.cast_time,
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
         }
     }
     pub fn with_recast_time(self, new_time: Moment) -> Self {
@@ -142,7 +191,57 @@ impl Action {
             off_gcd: self.off_gcd,
             animation_delay: self.animation_delay,
             cast_time: self.cast_time,
-            recast_time: new_time
+            recast_time: new_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs: self.costs
+        }
+    }
+    pub fn with_cooldown_group(self, group: u32) -> Self {
+        Self {
+            id: self.id,
+            target_type: self.target_type,
+            available: self.available,
+            effect: self.effect,
+            off_gcd: self.off_gcd,
+            animation_delay: self.animation_delay,
+            cast_time: self.cast_time,
+            recast_time: self.recast_time,
+            cooldown_group: Some(group),
+            max_charges: self.max_charges,
+            costs: self.costs
         }
     }
-}
\ No newline at end of file
+    pub fn with_max_charges(self, charges: u32) -> Self {
+        Self {
+            id: self.id,
+            target_type: self.target_type,
+            available: self.available,
+            effect: self.effect,
+            off_gcd: self.off_gcd,
+            animation_delay: self.animation_delay,
+            cast_time: self.cast_time,
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: charges.max(1),
+            costs: self.costs
+        }
+    }
+    pub fn with_cost(self, resource: &str, amount: u32) -> Self {
+        let mut costs = self.costs;
+        costs.push((resource.to_string(), amount));
+        Self {
+            id: self.id,
+            target_type: self.target_type,
+            available: self.available,
+            effect: self.effect,
+            off_gcd: self.off_gcd,
+            animation_delay: self.animation_delay,
+            cast_time: self.cast_time,
+            recast_time: self.recast_time,
+            cooldown_group: self.cooldown_group,
+            max_charges: self.max_charges,
+            costs
+        }
+    }
+}
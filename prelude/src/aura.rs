@@ -1,15 +1,16 @@
 use super::Entity;
 use super::Moment;
-use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+use uuid::Uuid;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Element {
     Fire,
     Earth,
     Unaspected
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum DamageType {
     Blunt,
     Slashing,
@@ -17,7 +18,7 @@ pub enum DamageType {
     Magic(Element)
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum SkillType {
     Auto,
     Skill,
@@ -26,22 +27,136 @@ pub enum SkillType {
 
 #[derive(Clone)]
 pub enum AuraEffect {
+    // Ticks on every server tick while the aura is up, as the source was when the aura went up
     DoT {
-        id: u32,
-        ticks: Arc<RwLock<Vec<Moment>>>,
         potency: u32,
         skill_type: SkillType,
         r#type: DamageType
     },
+    // Flat healing on every server tick while the aura is up
+    HoT {
+        amount: u32
+    },
+    // Damage dealt, in percent. Empty filters match everything
     DamageModifier {
         damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>,
         modifier: i32
     },
+    // Flat potency added to every matching hit
     PotencyModifier {
         damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>,
+        modifier: i32
+    },
+    // Damage taken by the entity carrying the aura, in percent
+    DamageTaken {
+        damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>,
+        modifier: i32
+    },
+    // Shortens cast times and GCD recasts by this many percent
+    Haste {
+        modifier: u32
+    },
+    // Critical and direct hit rates, in percent points, for the entity carrying the aura
+    CriticalHitRate {
+        damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>,
+        modifier: i32
+    },
+    DirectHitRate {
+        damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>,
+        modifier: i32
+    },
+    // The same, for everybody hitting the entity carrying the aura
+    CriticalHitRateTaken {
+        damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>,
         modifier: i32
+    },
+    DirectHitRateTaken {
+        damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>,
+        modifier: i32
+    },
+    GuaranteedCriticalHit {
+        damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>
+    },
+    GuaranteedDirectHit {
+        damage_type: Vec<DamageType>,
+        skill_type: Vec<SkillType>
+    }
+}
+impl AuraEffect {
+    pub fn is_periodic(&self) -> bool {
+        matches!(self, AuraEffect::DoT { .. } | AuraEffect::HoT { .. })
+    }
+    // The modifier of an effect, if it applies to this kind of hit
+    fn matching(damage_type: &[DamageType], skill_type: &[SkillType], modifier: i32, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match (damage_type.is_empty() || damage_type.contains(d_type)) && (skill_type.is_empty() || skill_type.contains(s_type)) {
+            true => Some(modifier),
+            false => None
+        }
+    }
+    pub fn critical_hit_rate(&self, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match self {
+            AuraEffect::CriticalHitRate { damage_type, skill_type, modifier } => Self::matching(damage_type, skill_type, *modifier, d_type, s_type),
+            _ => None
+        }
+    }
+    pub fn direct_hit_rate(&self, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match self {
+            AuraEffect::DirectHitRate { damage_type, skill_type, modifier } => Self::matching(damage_type, skill_type, *modifier, d_type, s_type),
+            _ => None
+        }
+    }
+    pub fn critical_hit_rate_taken(&self, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match self {
+            AuraEffect::CriticalHitRateTaken { damage_type, skill_type, modifier } => Self::matching(damage_type, skill_type, *modifier, d_type, s_type),
+            _ => None
+        }
+    }
+    pub fn direct_hit_rate_taken(&self, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match self {
+            AuraEffect::DirectHitRateTaken { damage_type, skill_type, modifier } => Self::matching(damage_type, skill_type, *modifier, d_type, s_type),
+            _ => None
+        }
+    }
+    pub fn guarantees_critical_hit(&self, d_type: &DamageType, s_type: &SkillType) -> bool {
+        match self {
+            AuraEffect::GuaranteedCriticalHit { damage_type, skill_type } => Self::matching(damage_type, skill_type, 0, d_type, s_type).is_some(),
+            _ => false
+        }
+    }
+    pub fn guarantees_direct_hit(&self, d_type: &DamageType, s_type: &SkillType) -> bool {
+        match self {
+            AuraEffect::GuaranteedDirectHit { damage_type, skill_type } => Self::matching(damage_type, skill_type, 0, d_type, s_type).is_some(),
+            _ => false
+        }
+    }
+    pub fn damage_modifier(&self, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match self {
+            AuraEffect::DamageModifier { damage_type, skill_type, modifier } => Self::matching(damage_type, skill_type, *modifier, d_type, s_type),
+            _ => None
+        }
+    }
+    pub fn potency_modifier(&self, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match self {
+            AuraEffect::PotencyModifier { damage_type, skill_type, modifier } => Self::matching(damage_type, skill_type, *modifier, d_type, s_type),
+            _ => None
+        }
+    }
+    pub fn damage_taken(&self, d_type: &DamageType, s_type: &SkillType) -> Option<i32> {
+        match self {
+            AuraEffect::DamageTaken { damage_type, skill_type, modifier } => Self::matching(damage_type, skill_type, *modifier, d_type, s_type),
+            _ => None
+        }
     }
 }
+
 #[derive(Clone)]
 pub struct Aura {
     pub id: u32,
@@ -49,5 +164,133 @@ pub struct Aura {
     pub target: Entity,
     pub start_time: Moment,
     pub end_time: Moment,
-    pub effects: Vec<AuraEffect>
-}
\ No newline at end of file
+    pub effects: Vec<AuraEffect>,
+    pub stacks: u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuraKind {
+    Buff,
+    Debuff
+}
+
+// What happens when an aura is applied on top of as many instances as it allows. Whichever way it goes, the
+// application's stacks are added to the instance's
+#[derive(Clone, Debug, PartialEq)]
+pub enum RefreshMode {
+    // The new application replaces the old one, snapshot included
+    Refresh,
+    // The new duration is added to what is left, up to this much from the moment of application
+    Extend(Moment),
+    Ignore
+}
+
+// Which instances of an aura a removal takes down
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemovalMode {
+    // Every instance from this source; for shared auras, which belong to nobody in particular, every instance
+    Source(Uuid),
+    All,
+    // The instance applied first; refreshing an instance makes it new again, extending it does not
+    Oldest,
+    // A single stack off the source's instance, the same as consuming one stack
+    Stack(Uuid)
+}
+
+#[derive(Clone)]
+pub struct AuraDefinition {
+    pub id: u32,
+    pub effects: Vec<AuraEffect>,
+    // Stacks an instance goes up with, and the most it can hold
+    pub initial_stacks: u32,
+    pub max_stacks: u32,
+    // How many instances can be up at once, per source or in total for shared auras
+    pub max_instances: u32,
+    pub refresh: RefreshMode,
+    // Whether every source keeps its own instances, like DoTs, or everybody shares them, like most debuffs
+    pub unique_per_source: bool,
+    pub kind: AuraKind
+}
+
+impl AuraDefinition {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            effects: vec![],
+            initial_stacks: 1,
+            max_stacks: 1,
+            max_instances: 1,
+            refresh: RefreshMode::Refresh,
+            unique_per_source: true,
+            kind: AuraKind::Buff
+        }
+    }
+    pub fn with_effects(self, effects: Vec<AuraEffect>) -> Self {
+        Self {
+            effects,
+            ..self
+        }
+    }
+    pub fn with_initial_stacks(self, initial_stacks: u32) -> Self {
+        Self {
+            initial_stacks: initial_stacks.max(1),
+            ..self
+        }
+    }
+    pub fn with_max_stacks(self, max_stacks: u32) -> Self {
+        Self {
+            max_stacks: max_stacks.max(1),
+            ..self
+        }
+    }
+    pub fn with_max_instances(self, max_instances: u32) -> Self {
+        Self {
+            max_instances: max_instances.max(1),
+            ..self
+        }
+    }
+    pub fn with_refresh(self, refresh: RefreshMode) -> Self {
+        Self {
+            refresh,
+            ..self
+        }
+    }
+    pub fn with_unique_per_source(self, unique_per_source: bool) -> Self {
+        Self {
+            unique_per_source,
+            ..self
+        }
+    }
+    pub fn with_kind(self, kind: AuraKind) -> Self {
+        Self {
+            kind,
+            ..self
+        }
+    }
+}
+
+// The definition of every aura id, so that applying an aura only has to name it
+#[derive(Clone, Default)]
+pub struct AuraCatalogue {
+    definitions: HashMap<u32, AuraDefinition>
+}
+
+impl AuraCatalogue {
+    pub fn new() -> Self {
+        Self {
+            definitions: HashMap::new()
+        }
+    }
+    pub fn with_definition(mut self, definition: AuraDefinition) -> Self {
+        self.definitions.insert(definition.id, definition);
+        self
+    }
+    // A single-instance buff that carries these effects
+    pub fn with_aura(self, id: u32, effects: Vec<AuraEffect>) -> Self {
+        self.with_definition(AuraDefinition::new(id).with_effects(effects))
+    }
+    // Unknown auras are single-instance buffs that carry nothing
+    pub fn definition(&self, id: &u32) -> AuraDefinition {
+        self.definitions.get(id).cloned().unwrap_or_else(|| AuraDefinition::new(*id))
+    }
+}
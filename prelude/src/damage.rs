@@ -3,13 +3,15 @@ use super::Effect;
 use super::aura::{DamageType, SkillType, Element};
 use super::Job;
 use super::Job::*;
+use super::Moment;
 use std::sync::{Arc, Mutex};
 use math::round::{floor, ceil};
-use rand::{StdRng, Rng};
-use rand::rngs::{ThreadRng};
+use rand::{Rng, SeedableRng};
+use rand::rngs::{ThreadRng, StdRng};
 pub trait Random {
     fn gen_f64(&mut self) -> f64;
 }
+pub type SharedRandom = Arc<Mutex<Box<dyn Random>>>;
 pub struct PassthroughRandom {
     inner: ThreadRng
 }
@@ -25,12 +27,31 @@ impl Random for PassthroughRandom {
         self.inner.gen()
     }
 }
-#[derive(Debug, PartialEq)]
+// Replays the exact same sequence of rolls for a given seed
+pub struct SeededRandom {
+    inner: StdRng
+}
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            inner: StdRng::seed_from_u64(seed)
+        }
+    }
+    pub fn shared(seed: u64) -> SharedRandom {
+        Arc::new(Mutex::new(Box::new(Self::new(seed))))
+    }
+}
+impl Random for SeededRandom {
+    fn gen_f64(&mut self) -> f64 {
+        self.inner.gen()
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
 pub enum AttackRoll {
     Hit(bool),
     CriticalHit(bool)
 }
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum DefenseRoll {
     Dodge,
     Parry,
@@ -44,269 +65,369 @@ pub struct RawDamage {
     pub range: (u32, u32),
     pub target: Entity,
     pub r#type: DamageType,
+    pub skill_type: SkillType,
     pub attack_roll: AttackRoll
 }
+#[derive(Clone)]
 pub struct AppliedDamage {
-    value: u32,
-    range: (u32, u32),
-    r#type: DamageType,
-    attack_roll: AttackRoll,
-    defense_roll: DefenseRoll
+    pub value: u32,
+    pub range: (u32, u32),
+    pub r#type: DamageType,
+    pub attack_roll: AttackRoll,
+    pub defense_roll: DefenseRoll
 }
 
 pub trait DamageStrategy {
     fn deal_damage(&self, source: &Entity, damage: Effect) -> RawDamage;
     fn apply_damage(&self, target: &Entity, damage: RawDamage) -> AppliedDamage;
+    // Cast times and GCD recasts go through here; strategies without speed math leave them untouched
+    fn scale_by_speed(&self, _source: &Entity, base: Moment) -> Moment {
+        base
+    }
+}
+
+// Averages a damage step over the 95%-105% variance, a tenth of a percent at a time, so that the
+// rounding at each step is accounted for
+fn variance_mean(damage_at: impl Fn(f64) -> f64) -> f64 {
+    (0..=100).map(|step| damage_at(0.95 + step as f64 / 1000.0)).sum::<f64>() / 101.0
+}
+
+// Random samples one outcome per hit; Expected returns the analytical mean and never touches the PRNG
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RollMode {
+    Random,
+    Expected
+}
+
+pub fn primary_stat(job: &Job) -> &'static str {
+    match job {
+        Job::DRG | Job::MNK | Job::WAR | Job::PLD | Job::DRK | Job::SAM => "Strength",
+        Job::BRD | Job::NIN | Job::MCH => "Dexterity",
+        Job::BLM | Job::SMN | Job::RDM => "Intelligence",
+        Job::WHM | Job::AST | Job::SCH => "Mind",
+        _ => "Strength"
+    }
+}
+// Jobs whose main stat is Intelligence or Mind, and who deal magic damage and scale with spell speed
+pub fn is_caster(job: &Job) -> bool {
+    matches!(primary_stat(job), "Intelligence" | "Mind")
+}
+
+// Level modifiers for the main and sub statistics and the divisor they scale by
+pub fn level_main(level: &u16) -> u32 {
+    match level {
+        1	=>	20	,
+        2	=>	21	,
+        3	=>	22	,
+        4	=>	24	,
+        5	=>	26	,
+        6	=>	27	,
+        7	=>	29	,
+        8	=>	31	,
+        9	=>	33	,
+        10	=>	35	,
+        11	=>	36	,
+        12	=>	38	,
+        13	=>	41	,
+        14	=>	44	,
+        15	=>	46	,
+        16	=>	49	,
+        17	=>	52	,
+        18	=>	54	,
+        19	=>	57	,
+        20	=>	60	,
+        21	=>	63	,
+        22	=>	67	,
+        23	=>	71	,
+        24	=>	74	,
+        25	=>	78	,
+        26	=>	81	,
+        27	=>	85	,
+        28	=>	89	,
+        29	=>	92	,
+        30	=>	97	,
+        31	=>	101	,
+        32	=>	106	,
+        33	=>	110	,
+        34	=>	115	,
+        35	=>	119	,
+        36	=>	124	,
+        37	=>	128	,
+        38	=>	134	,
+        39	=>	139	,
+        40	=>	144	,
+        41	=>	150	,
+        42	=>	155	,
+        43	=>	161	,
+        44	=>	166	,
+        45	=>	171	,
+        46	=>	177	,
+        47	=>	183	,
+        48	=>	189	,
+        49	=>	196	,
+        50	=>	202	,
+        51	=>	204	,
+        52	=>	205	,
+        53	=>	207	,
+        54	=>	209	,
+        55	=>	210	,
+        56	=>	212	,
+        57	=>	214	,
+        58	=>	215	,
+        59	=>	217	,
+        60	=>	218	,
+        61	=>	224	,
+        62	=>	228	,
+        63	=>	236	,
+        64	=>	244	,
+        65	=>	252	,
+        66	=>	260	,
+        67	=>	268	,
+        68	=>	276	,
+        69	=>	284	,
+        70	=>	292	,
+        _ => panic!()
+    }
+}
+pub fn level_sub(level: &u16) -> u32 {
+    match level {
+        1	=>	56	,
+        2	=>	57	,
+        3	=>	60	,
+        4	=>	62	,
+        5	=>	65	,
+        6	=>	68	,
+        7	=>	70	,
+        8	=>	73	,
+        9	=>	76	,
+        10	=>	78	,
+        11	=>	82	,
+        12	=>	85	,
+        13	=>	89	,
+        14	=>	93	,
+        15	=>	96	,
+        16	=>	100	,
+        17	=>	104	,
+        18	=>	109	,
+        19	=>	113	,
+        20	=>	116	,
+        21	=>	122	,
+        22	=>	127	,
+        23	=>	133	,
+        24	=>	138	,
+        25	=>	144	,
+        26	=>	150	,
+        27	=>	155	,
+        28	=>	162	,
+        29	=>	168	,
+        30	=>	173	,
+        31	=>	181	,
+        32	=>	188	,
+        33	=>	194	,
+        34	=>	202	,
+        35	=>	209	,
+        36	=>	215	,
+        37	=>	223	,
+        38	=>	229	,
+        39	=>	236	,
+        40	=>	244	,
+        41	=>	253	,
+        42	=>	263	,
+        43	=>	272	,
+        44	=>	283	,
+        45	=>	292	,
+        46	=>	302	,
+        47	=>	311	,
+        48	=>	322	,
+        49	=>	331	,
+        50	=>	341	,
+        51	=>	342	,
+        52	=>	344	,
+        53	=>	345	,
+        54	=>	346	,
+        55	=>	347	,
+        56	=>	349	,
+        57	=>	350	,
+        58	=>	351	,
+        59	=>	352	,
+        60	=>	354	,
+        61	=>	355	,
+        62	=>	356	,
+        63	=>	357	,
+        64	=>	358	,
+        65	=>	359	,
+        66	=>	360	,
+        67	=>	361	,
+        68	=>	362	,
+        69	=>	363	,
+        70	=>	364	,
+        _ => panic!()
+    }
+}
+pub fn level_div(level: &u16) -> u32 {
+    match level {
+        1	=>	56	,
+        2	=>	57	,
+        3	=>	60	,
+        4	=>	62	,
+        5	=>	65	,
+        6	=>	68	,
+        7	=>	70	,
+        8	=>	73	,
+        9	=>	76	,
+        10	=>	78	,
+        11	=>	82	,
+        12	=>	85	,
+        13	=>	89	,
+        14	=>	93	,
+        15	=>	96	,
+        16	=>	100	,
+        17	=>	104	,
+        18	=>	109	,
+        19	=>	113	,
+        20	=>	116	,
+        21	=>	122	,
+        22	=>	127	,
+        23	=>	133	,
+        24	=>	138	,
+        25	=>	144	,
+        26	=>	150	,
+        27	=>	155	,
+        28	=>	162	,
+        29	=>	168	,
+        30	=>	173	,
+        31	=>	181	,
+        32	=>	188	,
+        33	=>	194	,
+        34	=>	202	,
+        35	=>	209	,
+        36	=>	215	,
+        37	=>	223	,
+        38	=>	229	,
+        39	=>	236	,
+        40	=>	244	,
+        41	=>	253	,
+        42	=>	263	,
+        43	=>	272	,
+        44	=>	283	,
+        45	=>	292	,
+        46	=>	302	,
+        47	=>	311	,
+        48	=>	322	,
+        49	=>	331	,
+        50	=>	341	,
+        51	=>	393	,
+        52	=>	444	,
+        53	=>	496	,
+        54	=>	548	,
+        55	=>	600	,
+        56	=>	651	,
+        57	=>	703	,
+        58	=>	755	,
+        59	=>	806	,
+        60	=>	858	,
+        61	=>	941	,
+        62	=>	1032	,
+        63	=>	1133	,
+        64	=>	1243	,
+        65	=>	1364	,
+        66	=>	1497	,
+        67	=>	1643	,
+        68	=>	1802	,
+        69	=>	1978	,
+        70	=>	2170	,
+        _ => panic!()
+    }
 }
 
 pub struct AssumedDamageStrategy {
-    prng: Arc<Mutex<Box<Random>>>
+    prng: SharedRandom,
+    mode: RollMode
 }
 impl AssumedDamageStrategy {
     pub fn new() -> Self {
         Self {
-            prng: Arc::new(Mutex::new(Box::new(PassthroughRandom::new())))
+            prng: Arc::new(Mutex::new(Box::new(PassthroughRandom::new()))),
+            mode: RollMode::Random
+        }
+    }
+    pub fn with_random(prng: SharedRandom) -> Self {
+        Self {
+            prng,
+            mode: RollMode::Random
+        }
+    }
+    pub fn with_roll_mode(self, mode: RollMode) -> Self {
+        Self {
+            mode,
+            ..self
         }
     }
     pub fn primary_stat(&self, job:&Job) -> &str {
-        match job {
-            Job::DRG | Job::MNK | Job::WAR | Job::PLD | Job::DRK | Job::SAM => "Strength",
-            Job::BRD | Job::NIN | Job::MCH => "Dexterity",
-            Job::BLM | Job::SMN | Job::RDM => "Intelligence",
-            Job::WHM | Job::AST | Job::SCH => "Mind",
-            _ => "Strength"
+        primary_stat(job)
+    }
+    // Casters get their GCD from spell speed, everybody else from skill speed
+    pub fn speed_stat(&self, job: &Option<Job>) -> &str {
+        match job.as_ref().is_some_and(is_caster) {
+            true => "Spell Speed",
+            false => "Skill Speed"
         }
     }
+    // Speed above the level's base value, in thousandths. A missing statistic counts as the base value
+    pub fn speed_bonus(&self, source: &Entity, stat: &str) -> f64 {
+        let sub_modifier:f64 = self.level_sub(&source.level).into();
+        let div_modifier:f64 = self.level_div(&source.level).into();
+        floor(130.0 * (source.get_statistic(stat) as f64 - sub_modifier).max(0.0) / div_modifier, 0)
+    }
     pub fn level_main(&self, level: &u16) -> u32 {
-        match level {
-            1	=>	20	,
-            2	=>	21	,
-            3	=>	22	,
-            4	=>	24	,
-            5	=>	26	,
-            6	=>	27	,
-            7	=>	29	,
-            8	=>	31	,
-            9	=>	33	,
-            10	=>	35	,
-            11	=>	36	,
-            12	=>	38	,
-            13	=>	41	,
-            14	=>	44	,
-            15	=>	46	,
-            16	=>	49	,
-            17	=>	52	,
-            18	=>	54	,
-            19	=>	57	,
-            20	=>	60	,
-            21	=>	63	,
-            22	=>	67	,
-            23	=>	71	,
-            24	=>	74	,
-            25	=>	78	,
-            26	=>	81	,
-            27	=>	85	,
-            28	=>	89	,
-            29	=>	92	,
-            30	=>	97	,
-            31	=>	101	,
-            32	=>	106	,
-            33	=>	110	,
-            34	=>	115	,
-            35	=>	119	,
-            36	=>	124	,
-            37	=>	128	,
-            38	=>	134	,
-            39	=>	139	,
-            40	=>	144	,
-            41	=>	150	,
-            42	=>	155	,
-            43	=>	161	,
-            44	=>	166	,
-            45	=>	171	,
-            46	=>	177	,
-            47	=>	183	,
-            48	=>	189	,
-            49	=>	196	,
-            50	=>	202	,
-            51	=>	204	,
-            52	=>	205	,
-            53	=>	207	,
-            54	=>	209	,
-            55	=>	210	,
-            56	=>	212	,
-            57	=>	214	,
-            58	=>	215	,
-            59	=>	217	,
-            60	=>	218	,
-            61	=>	224	,
-            62	=>	228	,
-            63	=>	236	,
-            64	=>	244	,
-            65	=>	252	,
-            66	=>	260	,
-            67	=>	268	,
-            68	=>	276	,
-            69	=>	284	,
-            70	=>	292	,
-            _ => panic!()
-        }
+        level_main(level)
     }
     pub fn ap_div(&self, level: &u16) -> u32 { 
         125
     }
     pub fn level_sub(&self, level: &u16) -> u32 {
-        match level {
-            1	=>	56	,
-            2	=>	57	,
-            3	=>	60	,
-            4	=>	62	,
-            5	=>	65	,
-            6	=>	68	,
-            7	=>	70	,
-            8	=>	73	,
-            9	=>	76	,
-            10	=>	78	,
-            11	=>	82	,
-            12	=>	85	,
-            13	=>	89	,
-            14	=>	93	,
-            15	=>	96	,
-            16	=>	100	,
-            17	=>	104	,
-            18	=>	109	,
-            19	=>	113	,
-            20	=>	116	,
-            21	=>	122	,
-            22	=>	127	,
-            23	=>	133	,
-            24	=>	138	,
-            25	=>	144	,
-            26	=>	150	,
-            27	=>	155	,
-            28	=>	162	,
-            29	=>	168	,
-            30	=>	173	,
-            31	=>	181	,
-            32	=>	188	,
-            33	=>	194	,
-            34	=>	202	,
-            35	=>	209	,
-            36	=>	215	,
-            37	=>	223	,
-            38	=>	229	,
-            39	=>	236	,
-            40	=>	244	,
-            41	=>	253	,
-            42	=>	263	,
-            43	=>	272	,
-            44	=>	283	,
-            45	=>	292	,
-            46	=>	302	,
-            47	=>	311	,
-            48	=>	322	,
-            49	=>	331	,
-            50	=>	341	,
-            51	=>	342	,
-            52	=>	344	,
-            53	=>	345	,
-            54	=>	346	,
-            55	=>	347	,
-            56	=>	349	,
-            57	=>	350	,
-            58	=>	351	,
-            59	=>	352	,
-            60	=>	354	,
-            61	=>	355	,
-            62	=>	356	,
-            63	=>	357	,
-            64	=>	358	,
-            65	=>	359	,
-            66	=>	360	,
-            67	=>	361	,
-            68	=>	362	,
-            69	=>	363	,
-            70	=>	364	,
-            _ => panic!()
-        }
+        level_sub(level)
     }
     pub fn level_div(&self, level: &u16) -> u32 {
-        match level {
-            1	=>	56	,
-            2	=>	57	,
-            3	=>	60	,
-            4	=>	62	,
-            5	=>	65	,
-            6	=>	68	,
-            7	=>	70	,
-            8	=>	73	,
-            9	=>	76	,
-            10	=>	78	,
-            11	=>	82	,
-            12	=>	85	,
-            13	=>	89	,
-            14	=>	93	,
-            15	=>	96	,
-            16	=>	100	,
-            17	=>	104	,
-            18	=>	109	,
-            19	=>	113	,
-            20	=>	116	,
-            21	=>	122	,
-            22	=>	127	,
-            23	=>	133	,
-            24	=>	138	,
-            25	=>	144	,
-            26	=>	150	,
-            27	=>	155	,
-            28	=>	162	,
-            29	=>	168	,
-            30	=>	173	,
-            31	=>	181	,
-            32	=>	188	,
-            33	=>	194	,
-            34	=>	202	,
-            35	=>	209	,
-            36	=>	215	,
-            37	=>	223	,
-            38	=>	229	,
-            39	=>	236	,
-            40	=>	244	,
-            41	=>	253	,
-            42	=>	263	,
-            43	=>	272	,
-            44	=>	283	,
-            45	=>	292	,
-            46	=>	302	,
-            47	=>	311	,
-            48	=>	322	,
-            49	=>	331	,
-            50	=>	341	,
-            51	=>	393	,
-            52	=>	444	,
-            53	=>	496	,
-            54	=>	548	,
-            55	=>	600	,
-            56	=>	651	,
-            57	=>	703	,
-            58	=>	755	,
-            59	=>	806	,
-            60	=>	858	,
-            61	=>	941	,
-            62	=>	1032	,
-            63	=>	1133	,
-            64	=>	1243	,
-            65	=>	1364	,
-            66	=>	1497	,
-            67	=>	1643	,
-            68	=>	1802	,
-            69	=>	1978	,
-            70	=>	2170	,
-            _ => panic!()
+        level_div(level)
+    }
+}
+// Every hit deals its mean damage, so one run gives the mean DPS without any seed noise
+pub struct ExpectedDamageStrategy {
+    inner: AssumedDamageStrategy
+}
+impl ExpectedDamageStrategy {
+    pub fn new() -> Self {
+        Self {
+            inner: AssumedDamageStrategy::new().with_roll_mode(RollMode::Expected)
         }
     }
 }
+impl Default for ExpectedDamageStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl DamageStrategy for ExpectedDamageStrategy {
+    fn deal_damage(&self, source: &Entity, damage: Effect) -> RawDamage {
+        self.inner.deal_damage(source, damage)
+    }
+    fn apply_damage(&self, target: &Entity, damage: RawDamage) -> AppliedDamage {
+        self.inner.apply_damage(target, damage)
+    }
+    fn scale_by_speed(&self, source: &Entity, base: Moment) -> Moment {
+        self.inner.scale_by_speed(source, base)
+    }
+}
+
 impl DamageStrategy for AssumedDamageStrategy {
+    fn scale_by_speed(&self, source: &Entity, base: Moment) -> Moment {
+        let speed = self.speed_bonus(source, self.speed_stat(&source.job));
+        let scaled:f64 = floor((1000.0 - speed) * (base.as_millis() as f64) / 1000.0, 0);
+        let hasted:f64 = floor(scaled * (100.0 - source.haste() as f64) / 100.0, 0);
+        // The game only keeps hundredths of a second
+        Moment::from_millis((floor(hasted / 10.0, 0) * 10.0) as i64)
+    }
     fn apply_damage(&self, target:&Entity, damage: RawDamage) -> AppliedDamage {
         let mut rng = self.prng.lock().unwrap();
         let div_modifier:f64 = self.level_div(&target.level).into();
@@ -339,35 +460,35 @@ impl DamageStrategy for AssumedDamageStrategy {
         // TODO: fill these in
         let parry_chance:f64 = 0.0;
         let dodge_chance:f64 = 0.0;
-        let combat_roll = match &damage.attack_roll {
-            AttackRoll::Hit(direct) => match (target.can_dodge(&damage.ability), rng.gen_f64()) {
-                (true, v) if v>dodge_chance => match (target.can_block(&damage.ability), rng.gen_f64()) {
-                    (true, v) if v>block_chance => match (target.can_parry(&damage.ability), rng.gen_f64()) {
-                        (true, v) if v>parry_chance => DefenseRoll::Hit,
-                        _ => DefenseRoll::Parry
-                    },
-                    _ => DefenseRoll::Block
-                },
-                _ => DefenseRoll::Dodge
-            },
-            _ => {
-                // Is a crit and therefore cannot be blocked, parried or dodged
-                DefenseRoll::Hit
+        let block_strength:f64 = floor(30.0 * (target.get_statistic("Block Strength") as f64)/div_modifier + 10.0, 0)/100.0;
+        let (combat_roll, f_block) = match (self.mode, &damage.attack_roll) {
+            // Crits cannot be blocked, parried or dodged
+            (_, AttackRoll::CriticalHit(_)) => (DefenseRoll::Hit, 1.0),
+            (RollMode::Expected, _) => (DefenseRoll::Hit, match target.can_block(&damage.ability) {
+                true => 1.0 - block_chance / 100.0 * block_strength,
+                false => 1.0
+            }),
+            (RollMode::Random, AttackRoll::Hit(_)) => {
+                let mut rolls_under = |chance:f64| rng.gen_f64() < chance / 100.0;
+                match () {
+                    _ if target.can_dodge(&damage.ability) && rolls_under(dodge_chance) => (DefenseRoll::Dodge, 1.0),
+                    _ if target.can_block(&damage.ability) && rolls_under(block_chance) => (DefenseRoll::Block, 1.0 - block_strength),
+                    _ if target.can_parry(&damage.ability) && rolls_under(parry_chance) => (DefenseRoll::Parry, 1.0),
+                    _ => (DefenseRoll::Hit, 1.0)
+                }
             }
         };
-        let d:f64 = floor((damage.value as f64) * (1.0 - f_def) * (1.0 - f_res) * (2.0 - f_tnc) * (1.0 - (match &combat_roll {
-            DefenseRoll::Block => {
-                floor(30.0 * (target.get_statistic("Block Strength") as f64)/div_modifier + 10.0, 0)/100.0
-            },
-            _ => 0.0
-        })), 0);
+        let f_taken:f64 = target.damage_taken_modifier(&damage.r#type, &damage.skill_type);
+        let d:f64 = floor((damage.value as f64) * (1.0 - f_def) * (1.0 - f_res) * (2.0 - f_tnc) * f_taken * f_block, 0);
         let apply_buffs = |input:&f64, factor:f64| -> f64 {
             floor(input * factor, 0)
         };
-        let random_factor:f64 = rng.gen_f64() * 0.10 + 0.95;
         let min = apply_buffs(&d, 0.95);
         let max = apply_buffs(&d, 1.05);
-        let actual = apply_buffs(&d, random_factor);
+        let actual = match self.mode {
+            RollMode::Random => apply_buffs(&d, rng.gen_f64() * 0.10 + 0.95),
+            RollMode::Expected => variance_mean(|factor| apply_buffs(&d, factor)).round()
+        };
 
         AppliedDamage {
             value: actual as u32,
@@ -384,7 +505,7 @@ impl DamageStrategy for AssumedDamageStrategy {
         // Damage is guaranteed to be typed as Effect::Damage, but due to rust specifics, we need to cast it properly.
         // &Entity exists here for auras that do not snapshot.
         match damage {
-            Effect::Damage { source, target, potency, r#type, skill_type, action: action_id, periodic } => {
+            Effect::Damage { source: _, target, potency, r#type, skill_type, action: action_id, periodic } => {
                 // Damage calculations are done differently between periodic and non-periodic damage,
                 // as non-periodic already takes into account skill/spell speed on cast and recast 
                 // times
@@ -392,7 +513,7 @@ impl DamageStrategy for AssumedDamageStrategy {
                 // First, let's calculate a few things
                     
                 // Potency is easy:
-                let new_pot:f64 = source.potency_modifier(&action_id, potency) as f64;
+                let new_pot:f64 = source.potency_modifier(&action_id, &r#type, &skill_type, potency) as f64;
                 let f_pot:f64 = new_pot/100.0;
 
                 // Our first split is on which weapon damage to take into account.
@@ -416,18 +537,18 @@ impl DamageStrategy for AssumedDamageStrategy {
                     SkillType::Auto | SkillType::Skill => "Attack Power",
                     _ => "Magic Attack Power"
                 }) as f64) - (modifier as f64)) * 10000.0)/(80.0 * (modifier as f64)), 0);
-                println!("div {:?} ap {:?} mod {:?} inter {:?}", ap_div, (source.get_statistic(match r#skill_type {
-                    SkillType::Auto | SkillType::Skill => "Attack Power",
-                    _ => "Magic Attack Power"
-                }) as f64), modifier, inter_f_ap);
                 let f_ap:f64 = (100.0 + inter_f_ap)/100.0;
                 let inter_det:f64 = floor((130.0 as f64) * (source.get_statistic("Determination") as f64 -modifier as f64)/div_modifier,0)+1000.0;
                 let inter_tnc:f64 = floor((100.0 as f64) * ((source.get_statistic("Tenacity") as f64 -sub_modifier))/div_modifier, 0)+1000.0;
                 let f_det:f64 = floor(inter_det,0)/1000.0;
                 let f_tnc:f64 = floor(inter_tnc,0)/1000.0;
-                let f_ss:f64 = match periodic {
-                    true => 1.0,
-                    false => 1.0
+                // Speed only shows up in damage for DoTs and auto-attacks; everything else is sped up through its timings
+                let f_ss:f64 = match (periodic, &skill_type) {
+                    (true, _) | (false, SkillType::Auto) => (1000.0 + self.speed_bonus(source, match r#type {
+                        DamageType::Magic(_) => "Spell Speed",
+                        _ => "Skill Speed"
+                    })) / 1000.0,
+                    _ => 1.0
                 };
                 let f_traits:f64 = source.get_traits_for_ability_damage(&r#type, &skill_type, action_id);
 
@@ -436,21 +557,22 @@ impl DamageStrategy for AssumedDamageStrategy {
                 let inter_dhc:f64 = 550.0 * (source.get_statistic("Direct Hit Rate") as f64 - sub_modifier)/div_modifier + 50.0;
                 let base_chc:f64 = floor(inter_chc, 0)/10.0;
                 let base_dhc:f64 = floor(inter_dhc, 0)/10.0;
-                let additional_chc_from_traits:f64 = source.get_extra_ability_chc(&r#type, &skill_type, &action_id);
+                let additional_chc_from_traits:f64 = source.get_extra_ability_chc(&target, &r#type, &skill_type, &action_id);
                 
-                let additional_dhc_from_traits:f64 = source.get_extra_ability_dhc(&r#type, &skill_type, &action_id);
+                let additional_dhc_from_traits:f64 = source.get_extra_ability_dhc(&target, &r#type, &skill_type, &action_id);
                 let dhc:f64 = base_dhc + additional_dhc_from_traits;
                 let chc:f64 = base_chc + additional_chc_from_traits;
 
                 let inter_chr:f64 = 200.0 * (source.get_statistic("Critical Hit Rate") as f64 - sub_modifier)/div_modifier + 1000.0;
                 let f_chr:f64 = floor(inter_chr, 0)/1000.0;
-                // This tells us what we rolled offensively
-                let roll1:f64 = rng.gen_f64();
-                let roll2:f64 = rng.gen_f64();
-                let is_direct = (roll1- dhc) >= 0.0;
-                let damage_type = match (roll2 - dhc) >= 0.0 {
-                    true => AttackRoll::CriticalHit(is_direct),
-                    _  => AttackRoll::Hit(is_direct)
+                // Chances are worked out in percent; rolls are against probabilities
+                let p_direct:f64 = match source.guaranteed_direct_hit(&r#type, &skill_type) {
+                    true => 1.0,
+                    false => (dhc / 100.0).clamp(0.0, 1.0)
+                };
+                let p_critical:f64 = match source.guaranteed_critical_hit(&r#type, &skill_type) {
+                    true => 1.0,
+                    false => (chc / 100.0).clamp(0.0, 1.0)
                 };
                 // From here, we have enough for the first half of the damage calculation:
                 let d:f64 = floor(
@@ -461,42 +583,65 @@ impl DamageStrategy for AssumedDamageStrategy {
                     f_tnc *
                     f_traits
                 , 0);
-                let random_factor:f64 = rng.gen_f64() * 0.10 + 0.95;
-                let apply_damage = |input:f64, factor| {
+                let apply_damage = |roll: &AttackRoll, factor: f64| {
+                    let f_crit:f64 = match roll {
+                        AttackRoll::CriticalHit(_) => f_chr,
+                        AttackRoll::Hit(_) => 1.0
+                    };
+                    let f_direct:f64 = match roll {
+                        AttackRoll::CriticalHit(true) | AttackRoll::Hit(true) => 1.25,
+                        _ => 1.0
+                    };
                     let d:f64 = match periodic {
                         true => {
                             let d:f64 = floor(d * f_ss, 0);
                             let d:f64 = floor(d * factor, 0);
-                            let d:f64 = floor(d * (match damage_type {
-                                AttackRoll::CriticalHit(_) => f_chr,
-                                AttackRoll::Hit(_) => 1.0
-                            }), 0);
-                            floor(d * (match damage_type {
-                                AttackRoll::CriticalHit(v) | AttackRoll::Hit(v) if v == true => 1.25,
-                                _ => 1.0
-                            }), 0)
+                            let d:f64 = floor(d * f_crit, 0);
+                            floor(d * f_direct, 0)
                         },
                         false => {
-                            let d:f64 = floor(d * (match damage_type {
-                                AttackRoll::CriticalHit(_) => f_chr,
-                                AttackRoll::Hit(_) => 1.0
-                            }), 0);
-                            let d:f64 = floor(d * (match damage_type {
-                                AttackRoll::CriticalHit(v) | AttackRoll::Hit(v) if v == true => 1.25,
-                                _ => 1.0
-                            }), 0);
+                            let d:f64 = floor(d * f_ss, 0);
+                            let d:f64 = floor(d * f_crit, 0);
+                            let d:f64 = floor(d * f_direct, 0);
                             floor(d * factor, 0)
                         }
                     };
-                    source.modify_damage_from_ability(&action_id, &r#type, &skill_type, (d/1.0) as u32)
+                    source.modify_damage_from_ability(&action_id, &r#type, &skill_type, d as u32)
+                };
+                let (damage_type, actual) = match self.mode {
+                    RollMode::Random => {
+                        // This tells us what we rolled offensively
+                        let is_critical = rng.gen_f64() < p_critical;
+                        let is_direct = rng.gen_f64() < p_direct;
+                        let damage_type = match is_critical {
+                            true => AttackRoll::CriticalHit(is_direct),
+                            false => AttackRoll::Hit(is_direct)
+                        };
+                        let random_factor:f64 = rng.gen_f64() * 0.10 + 0.95;
+                        let actual = apply_damage(&damage_type, random_factor);
+                        (damage_type, actual)
+                    },
+                    RollMode::Expected => {
+                        // The mean over the four outcomes and the whole variance range
+                        let outcomes = [
+                            (AttackRoll::Hit(false), (1.0 - p_critical) * (1.0 - p_direct)),
+                            (AttackRoll::Hit(true), (1.0 - p_critical) * p_direct),
+                            (AttackRoll::CriticalHit(false), p_critical * (1.0 - p_direct)),
+                            (AttackRoll::CriticalHit(true), p_critical * p_direct)
+                        ];
+                        let mean:f64 = outcomes.iter().map(|(roll, p)| {
+                            variance_mean(|factor| apply_damage(roll, factor) as f64) * p
+                        }).sum();
+                        (AttackRoll::Hit(false), mean.round() as u32)
+                    }
                 };
-                let min = apply_damage(d, 0.95);
-                let max = apply_damage(d, 1.05);
-                let actual = apply_damage(d, random_factor);
+                let min = apply_damage(&damage_type, 0.95);
+                let max = apply_damage(&damage_type, 1.05);
                 RawDamage {
                     value: actual,
                     range: (min, max),
                     r#type: r#type,
+                    skill_type,
                     ability: action_id,
                     target: target,
                     attack_roll: damage_type
@@ -510,13 +655,20 @@ impl DamageStrategy for AssumedDamageStrategy {
 #[cfg(test)]
 mod tests {
     use super::DamageStrategy;
-    use super::{Entity, AssumedDamageStrategy, AttackRoll, SkillType, Job, DamageType, Effect};
-    use std::sync::Arc;
-    #[test]
-    fn base_damage_checks_out() {
+    use super::{Entity, AssumedDamageStrategy, AttackRoll, SkillType, Job, DamageType, Effect, Random, SeededRandom, Moment, RollMode, ExpectedDamageStrategy};
+    use crate::{Aura, AuraEffect, AuraDefinition, Element};
+    use crate::entity::Trait;
+    use std::sync::{Arc, Mutex};
+
+    struct FixedRandom(f64);
+    impl Random for FixedRandom {
+        fn gen_f64(&mut self) -> f64 {
+            self.0
+        }
+    }
+
+    fn dark_knight() -> Entity {
         let mut red_mage = Entity::create("red_mage".to_string(), Some(Job::DRK), 70, vec![], Arc::new(vec![]));
-        let mut target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
-        let strat = AssumedDamageStrategy::new();
         red_mage.set_statistic("Strength", 2011);
         red_mage.set_statistic("Critical Hit Rate", 1155);
         red_mage.set_statistic("Determination", 1834);
@@ -526,24 +678,300 @@ mod tests {
         red_mage.set_statistic("Spell Speed", 364);
         red_mage.set_statistic("Tenacity", 1223);
         red_mage.set_statistic("Physical Damage", 105);
-        let effect = Effect::Damage {
-            source: red_mage.clone(),
+        red_mage
+    }
+    fn slash(source: &Entity, target: &Entity) -> Effect {
+        Effect::Damage {
+            source: source.clone(),
             target: target.clone(),
             potency: 150,
             r#type: DamageType::Slashing,
             skill_type: SkillType::Skill,
             action: 2,
             periodic: false
-        };
-        let mut hit = None;
-        while (!hit.is_some()) {
-            let output = strat.deal_damage(&red_mage,effect.clone());
-            if output.attack_roll == AttackRoll::Hit(false) {
-                hit = Some(output);
-            }
         }
-        let actual_hit = hit.unwrap();
+    }
+    #[test]
+    fn base_damage_checks_out() {
+        let red_mage = dark_knight();
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let strat = AssumedDamageStrategy::with_random(SeededRandom::shared(1));
+        let effect = slash(&red_mage, &target);
+        let actual_hit = strat.deal_damage(&red_mage, effect);
+        assert_eq!(actual_hit.attack_roll, AttackRoll::Hit(false));
         assert!(1827 > actual_hit.range.0 && 2008 < actual_hit.range.1 && (((2008-1827)/(actual_hit.range.1-actual_hit.range.0)) as f64) < 0.01);
         // First, we set our stats
     }
+    #[test]
+    fn fixed_rolls_give_exact_damage() {
+        let red_mage = dark_knight();
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        // Every roll lands in the middle, so the variance factor is exactly 1
+        let strat = AssumedDamageStrategy::with_random(Arc::new(Mutex::new(Box::new(FixedRandom(0.5)))));
+        let raw = strat.deal_damage(&red_mage, slash(&red_mage, &target));
+        assert_eq!(raw.value, 1918);
+        let applied = strat.apply_damage(&target, raw);
+        assert_eq!(applied.value, 1950);
+    }
+    #[test]
+    fn same_seed_replays_the_same_rolls() {
+        let red_mage = dark_knight();
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let roll_all = |seed| {
+            let strat = AssumedDamageStrategy::with_random(SeededRandom::shared(seed));
+            (0..20).map(|_| {
+                let raw = strat.deal_damage(&red_mage, slash(&red_mage, &target));
+                strat.apply_damage(&target, raw).value
+            }).collect::<Vec<u32>>()
+        };
+        assert_eq!(roll_all(42), roll_all(42));
+        assert_ne!(roll_all(42), roll_all(43));
+    }
+    #[test]
+    fn speed_shortens_the_gcd() {
+        let mut dark_knight = dark_knight();
+        let strat = AssumedDamageStrategy::new();
+        // 603 skill speed is 14 thousandths over the level 70 base: 2465ms, kept to the hundredth
+        assert_eq!(strat.scale_by_speed(&dark_knight, Moment::new(2, 500)), Moment::new(2, 460));
+        // Spell speed does nothing for a job on skill speed
+        dark_knight.set_statistic("Spell Speed", 3000);
+        assert_eq!(strat.scale_by_speed(&dark_knight, Moment::new(2, 500)), Moment::new(2, 460));
+        let haste = Aura {
+            id: 1,
+            source: dark_knight.clone(),
+            target: dark_knight.clone(),
+            start_time: Moment::new(0, 0),
+            end_time: Moment::new(20, 0),
+            effects: vec![AuraEffect::Haste { modifier: 20 }],
+            stacks: 1
+        };
+        dark_knight.add_aura(haste, &AuraDefinition::new(1));
+        assert_eq!(strat.scale_by_speed(&dark_knight, Moment::new(2, 500)), Moment::new(1, 970));
+        // Without any speed on the entity, the base GCD stays as it is
+        let target = Entity::create("target".to_string(), None, 70, vec![], Arc::new(vec![]));
+        assert_eq!(strat.scale_by_speed(&target, Moment::new(2, 500)), Moment::new(2, 500));
+    }
+    #[test]
+    fn speed_scales_dots() {
+        let dark_knight = dark_knight();
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let strat = AssumedDamageStrategy::with_random(Arc::new(Mutex::new(Box::new(FixedRandom(0.5)))));
+        let tick = |periodic| strat.deal_damage(&dark_knight, Effect::Damage {
+            source: dark_knight.clone(),
+            target: target.clone(),
+            potency: 150,
+            r#type: DamageType::Slashing,
+            skill_type: SkillType::Skill,
+            action: 2,
+            periodic
+        }).value;
+        assert_eq!(tick(false), 1918);
+        // A 1.014 speed factor on top of the same hit
+        assert_eq!(tick(true), 1944);
+    }
+    fn aura_on(entity: &Entity, id: u32, effects: Vec<AuraEffect>) -> Aura {
+        Aura {
+            id,
+            source: entity.clone(),
+            target: entity.clone(),
+            start_time: Moment::new(0, 0),
+            end_time: Moment::new(20, 0),
+            effects,
+            stacks: 1
+        }
+    }
+    #[test]
+    fn auras_modify_damage() {
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let strat = AssumedDamageStrategy::with_random(Arc::new(Mutex::new(Box::new(FixedRandom(0.5)))));
+        let hit = |source: &Entity| strat.deal_damage(source, slash(source, &target)).value;
+        let mut buffed = dark_knight();
+        // Buffs for other kinds of hits do nothing
+        buffed.add_aura(aura_on(&buffed, 1, vec![AuraEffect::DamageModifier {
+            damage_type: vec![DamageType::Magic(Element::Unaspected)],
+            skill_type: vec![],
+            modifier: 50
+        }, AuraEffect::DamageModifier {
+            damage_type: vec![],
+            skill_type: vec![SkillType::Spell],
+            modifier: 50
+        }]), &AuraDefinition::new(1));
+        assert_eq!(hit(&buffed), 1918);
+        buffed.add_aura(aura_on(&buffed, 2, vec![AuraEffect::DamageModifier {
+            damage_type: vec![DamageType::Slashing],
+            skill_type: vec![SkillType::Skill],
+            modifier: 10
+        }]), &AuraDefinition::new(2).with_max_instances(2));
+        assert_eq!(hit(&buffed), 2109);
+        // A second copy of the same aura does not stack, another aura multiplies
+        buffed.add_aura(aura_on(&buffed, 2, vec![AuraEffect::DamageModifier {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 5
+        }]), &AuraDefinition::new(2).with_max_instances(2));
+        assert_eq!(hit(&buffed), 2109);
+        buffed.add_aura(aura_on(&buffed, 3, vec![AuraEffect::DamageModifier {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 10
+        }]), &AuraDefinition::new(3));
+        assert_eq!(hit(&buffed), 2320);
+
+        let mut boosted = dark_knight();
+        boosted.add_aura(aura_on(&boosted, 4, vec![AuraEffect::PotencyModifier {
+            damage_type: vec![],
+            skill_type: vec![SkillType::Skill],
+            modifier: 150
+        }]), &AuraDefinition::new(4));
+        // Twice the potency, give or take the rounding
+        assert_eq!(hit(&boosted), 3836);
+
+        let mut trained = dark_knight();
+        trained.traits.push(Trait {
+            id: 1,
+            effects: vec![AuraEffect::DamageModifier {
+                damage_type: vec![],
+                skill_type: vec![],
+                modifier: 20
+            }]
+        });
+        assert_eq!(hit(&trained), 2302);
+    }
+    #[test]
+    fn stacks_scale_aura_modifiers() {
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let strat = AssumedDamageStrategy::with_random(Arc::new(Mutex::new(Box::new(FixedRandom(0.5)))));
+        let hit = |source: &Entity| strat.deal_damage(source, slash(source, &target)).value;
+        let mut stacked = dark_knight();
+        let definition = AuraDefinition::new(6).with_max_stacks(3);
+        stacked.add_aura(aura_on(&stacked, 6, vec![AuraEffect::DamageModifier {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 10
+        }]), &definition);
+        assert_eq!(hit(&stacked), 2109);
+        // Three stacks of +10% make +30%
+        let id = stacked.id;
+        stacked.add_stacks(&6, id, 2, &definition);
+        assert_eq!(hit(&stacked), 2493);
+    }
+    #[test]
+    fn debuffs_on_the_target_raise_damage_taken() {
+        let dark_knight = dark_knight();
+        let mut target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let strat = AssumedDamageStrategy::with_random(Arc::new(Mutex::new(Box::new(FixedRandom(0.5)))));
+        let applied = |target: &Entity| {
+            let raw = strat.deal_damage(&dark_knight, slash(&dark_knight, target));
+            strat.apply_damage(target, raw).value
+        };
+        assert_eq!(applied(&target), 1950);
+        target.add_aura(aura_on(&target, 5, vec![AuraEffect::DamageTaken {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 10
+        }]), &AuraDefinition::new(5));
+        assert_eq!(applied(&target), 2145);
+    }
+    #[test]
+    fn crit_and_direct_hit_auras() {
+        let mut dark_knight = dark_knight();
+        let mut target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let slashing = (DamageType::Slashing, SkillType::Skill);
+        assert_eq!(dark_knight.get_extra_ability_chc(&target, &slashing.0, &slashing.1, &2), 0.0);
+        dark_knight.add_aura(aura_on(&dark_knight, 1, vec![AuraEffect::CriticalHitRate {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 10
+        }, AuraEffect::DirectHitRate {
+            damage_type: vec![],
+            skill_type: vec![SkillType::Spell],
+            modifier: 20
+        }]), &AuraDefinition::new(1));
+        // A debuff on the target counts for whoever hits it
+        target.add_aura(aura_on(&target, 2, vec![AuraEffect::CriticalHitRateTaken {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 5
+        }, AuraEffect::DirectHitRateTaken {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 3
+        }]), &AuraDefinition::new(2));
+        assert_eq!(dark_knight.get_extra_ability_chc(&target, &slashing.0, &slashing.1, &2), 15.0);
+        assert_eq!(dark_knight.get_extra_ability_dhc(&target, &slashing.0, &slashing.1, &2), 3.0);
+
+        let strat = AssumedDamageStrategy::with_random(Arc::new(Mutex::new(Box::new(FixedRandom(0.5)))));
+        assert_eq!(strat.deal_damage(&dark_knight, slash(&dark_knight, &target)).attack_roll, AttackRoll::Hit(false));
+        dark_knight.add_aura(aura_on(&dark_knight, 3, vec![AuraEffect::GuaranteedCriticalHit {
+            damage_type: vec![],
+            skill_type: vec![SkillType::Skill]
+        }, AuraEffect::GuaranteedDirectHit {
+            damage_type: vec![],
+            skill_type: vec![SkillType::Skill]
+        }]), &AuraDefinition::new(3));
+        let raw = strat.deal_damage(&dark_knight, slash(&dark_knight, &target));
+        assert_eq!(raw.attack_roll, AttackRoll::CriticalHit(true));
+        assert!(raw.value > 1918);
+    }
+    #[test]
+    fn crit_and_direct_hit_roll_at_their_own_rates() {
+        let mut dark_knight = dark_knight();
+        // 12.2% crit from the stats; 6.4% direct hit plus 40 points from a buff
+        dark_knight.add_aura(aura_on(&dark_knight, 1, vec![AuraEffect::DirectHitRate {
+            damage_type: vec![],
+            skill_type: vec![],
+            modifier: 40
+        }]), &AuraDefinition::new(1));
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let strat = AssumedDamageStrategy::with_random(SeededRandom::shared(7));
+        let rolls:Vec<AttackRoll> = (0..20000).map(|_| strat.deal_damage(&dark_knight, slash(&dark_knight, &target)).attack_roll).collect();
+        let rate = |check: &dyn Fn(&AttackRoll) -> bool| rolls.iter().filter(|roll| check(roll)).count() as f64 / rolls.len() as f64;
+        let critical = rate(&|roll| matches!(roll, AttackRoll::CriticalHit(_)));
+        let direct = rate(&|roll| matches!(roll, AttackRoll::CriticalHit(true) | AttackRoll::Hit(true)));
+        let both = rate(&|roll| roll == &AttackRoll::CriticalHit(true));
+        assert!((critical - 0.122).abs() < 0.01, "crit rate {}", critical);
+        assert!((direct - 0.464).abs() < 0.01, "direct hit rate {}", direct);
+        assert!((both - 0.122 * 0.464).abs() < 0.01, "critical direct hit rate {}", both);
+    }
+    #[test]
+    fn expected_mode_returns_the_mean() {
+        let dark_knight = dark_knight();
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let expected = AssumedDamageStrategy::with_random(SeededRandom::shared(3)).with_roll_mode(RollMode::Expected);
+        let mean = expected.deal_damage(&dark_knight, slash(&dark_knight, &target)).value as f64;
+        let random = AssumedDamageStrategy::with_random(SeededRandom::shared(3));
+        let samples:f64 = (0..20000).map(|_| random.deal_damage(&dark_knight, slash(&dark_knight, &target)).value as f64).sum();
+        assert!((samples / 20000.0 - mean).abs() / mean < 0.005);
+        // The mean does not move between calls
+        assert_eq!(expected.deal_damage(&dark_knight, slash(&dark_knight, &target)).value as f64, mean);
+
+        // With both outcomes guaranteed, the mean is that one outcome, within a point of the middle of the variance
+        let mut guaranteed = dark_knight.clone();
+        guaranteed.add_aura(aura_on(&guaranteed, 2, vec![AuraEffect::GuaranteedCriticalHit {
+            damage_type: vec![],
+            skill_type: vec![]
+        }, AuraEffect::GuaranteedDirectHit {
+            damage_type: vec![],
+            skill_type: vec![]
+        }]), &AuraDefinition::new(2));
+        let fixed = AssumedDamageStrategy::with_random(Arc::new(Mutex::new(Box::new(FixedRandom(0.5)))));
+        let mean = expected.deal_damage(&guaranteed, slash(&guaranteed, &target)).value as i64;
+        let middle = fixed.deal_damage(&guaranteed, slash(&guaranteed, &target)).value as i64;
+        assert!((mean - middle).abs() <= 1);
+    }
+    #[test]
+    fn expected_strategy_matches_the_sampled_mean() {
+        let dark_knight = dark_knight();
+        let target = Entity::create("red_mage".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let expected = ExpectedDamageStrategy::new();
+        let applied = |strat: &dyn DamageStrategy| {
+            let raw = strat.deal_damage(&dark_knight, slash(&dark_knight, &target));
+            strat.apply_damage(&target, raw).value as f64
+        };
+        let mean = applied(&expected);
+        let random = AssumedDamageStrategy::with_random(SeededRandom::shared(11));
+        let samples:f64 = (0..20000).map(|_| applied(&random)).sum();
+        assert!((samples / 20000.0 - mean).abs() / mean < 0.005);
+        assert_eq!(applied(&expected), mean);
+    }
 }
\ No newline at end of file
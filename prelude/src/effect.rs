@@ -1,4 +1,4 @@
-use super::{Entity, Moment, Action, SkillType, DamageType};
+use super::{Entity, Moment, Action, SkillType, DamageType, AuraEffect, AuraKind, RemovalMode};
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -7,12 +7,31 @@ pub enum Effect {
         source: Entity,
         target: Entity,
         aura: u32,
-        duration: Moment
+        duration: Moment,
+        effects: Vec<AuraEffect>
     },
     RemoveAura {
+        target: Entity,
+        aura: u32,
+        mode: RemovalMode
+    },
+    // Every aura of the kind comes off the target, whoever applied it
+    Cleanse {
+        target: Entity,
+        kind: AuraKind
+    },
+    // Stacks go to, and come off, the source's instance of the aura
+    AddStacks {
+        source: Entity,
+        target: Entity,
+        aura: u32,
+        stacks: u32
+    },
+    ConsumeStacks {
         source: Entity,
         target: Entity,
-        aura: u32
+        aura: u32,
+        stacks: u32
     },
     Damage {
         source: Entity,
@@ -23,11 +42,21 @@ pub enum Effect {
         r#type: DamageType,
         periodic: bool
     },
+    Heal {
+        source: Entity,
+        target: Entity,
+        amount: u32
+    },
     ModifyResource {
         target: Entity,
         resource: String,
         amount: i32
     },
+    StartRecast {
+        source: Entity,
+        action: Action,
+        start: Moment
+    },
     BeginCast {
         source: Entity,
         target: Entity,
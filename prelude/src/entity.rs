@@ -1,4 +1,5 @@
 use super::Action;
+use super::GCD_COOLDOWN_GROUP;
 use super::AuraEffect;
 use super::Moment;
 use super::ConditionalAction;
@@ -7,20 +8,52 @@ use std::collections::HashMap;
 use uuid::Uuid;
 use crate::{SkillType, DamageType};
 use super::Aura;
+use crate::{AuraCatalogue, AuraDefinition, AuraKind, RefreshMode, RemovalMode};
 use super::Effect;
 use crate::SimError;
 
+// Passive job bonuses; they work like an aura that never falls off
 #[derive(Clone)]
 pub struct Trait {
+    pub id: u32,
+    pub effects: Vec<AuraEffect>
+}
+
+// The action id auto-attacks are reported under
+pub const AUTO_ATTACK: u32 = 7;
+
+// A weapon swinging on its own timer, independently of the APL
+#[derive(Clone)]
+pub struct AutoAttack {
+    pub delay: Moment,
+    pub potency: u32,
+    pub r#type: DamageType
 }
 
 #[derive(Clone)]
 pub struct Resource {
     name: String,
     current_value: u32,
-    max_value: u32
+    max_value: u32,
+    // Gained (or lost) on every server tick
+    regen: i32
 }
 impl Resource {
+    fn new(name: &str, current_value: u32, max_value: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            current_value: current_value.min(max_value),
+            max_value,
+            regen: 0
+        }
+    }
+    // What the next server tick actually changes, once the bounds are taken into account
+    fn regen_step(&self) -> i32 {
+        match self.regen >= 0 {
+            true => self.regen.min((self.max_value - self.current_value).min(i32::MAX as u32) as i32),
+            false => self.regen.max(-(self.current_value.min(i32::MAX as u32) as i32))
+        }
+    }
     fn modify(&mut self, modifier: i32) {
         let intermediate = (self.current_value as i32) + modifier;
         match intermediate < 0 {
@@ -103,6 +136,22 @@ impl PartialEq for Status {
     }
 }
 
+// GCD actions share the global cooldown unless they belong to a group; other actions run their own timer
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum RecastTimer {
+    Group(u32),
+    Action(u32)
+}
+impl RecastTimer {
+    fn of(action: &Action) -> Self {
+        match (action.cooldown_group, action.off_gcd) {
+            (Some(group), _) => RecastTimer::Group(group),
+            (None, false) => RecastTimer::Group(GCD_COOLDOWN_GROUP),
+            (None, true) => RecastTimer::Action(action.id)
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Entity {
     pub id: Uuid,
@@ -110,12 +159,15 @@ pub struct Entity {
     pub level: u16,
     pub job: Option<Job>,
     pub status: Status,
+    pub hostile: bool,
     pub last_auto: Moment,
+    pub auto_attack: Option<AutoAttack>,
     pub traits: Vec<Trait>,
     pub auras: HashMap<u32, Vec<Aura>>,
-    last_tick: HashMap<(u32, Uuid), Moment>,
     statistics: HashMap<String, u32>,
     resources: HashMap<String, Resource>,
+    health: Option<Resource>,
+    recasts: HashMap<RecastTimer, Moment>,
     action_repository: Arc<Vec<Action>>,
     action_list: Vec<ConditionalAction>
 }
@@ -141,30 +193,134 @@ impl Entity {
     pub fn set_statistic(&mut self, name:&str, value:u32) {
         self.statistics.insert(name.to_string(), value);
     }
-    pub fn remove_aura(&mut self, id:&u32, source: Option<Uuid>) {
-        match self.auras.get_mut(id) {
-            Some(ref mut aura_list) => aura_list.retain(|e| match source {
-                Some(uuid) => {
-                    e.source.id != uuid
-                },
-                None => false
-            }),
-            None => ()
+    // Entities without a health pool (training dummies, mostly) can never die
+    pub fn set_max_hp(&mut self, max_hp: u32) {
+        self.health = Some(Resource::new("HP", max_hp, max_hp));
+    }
+    pub fn hp(&self) -> Option<u32> {
+        self.health.as_ref().map(|health| health.current_value)
+    }
+    pub fn max_hp(&self) -> Option<u32> {
+        self.health.as_ref().map(|health| health.max_value)
+    }
+    pub fn is_alive(&self) -> bool {
+        self.hp() != Some(0)
+    }
+    pub fn take_damage(&mut self, amount: u32) {
+        if let Some(ref mut health) = self.health {
+            health.modify(-(amount.min(i32::MAX as u32) as i32));
         }
     }
-    pub fn add_aura(&mut self, aura: Aura) {
-        let mut aura_list = self.auras.entry(aura.id).or_insert_with(|| vec![]);
-        aura_list.push(aura);
+    // Heals do not bring anything back from zero
+    pub fn heal(&mut self, amount: u32) {
+        if self.is_alive() {
+            if let Some(ref mut health) = self.health {
+                health.modify(amount.min(i32::MAX as u32) as i32);
+            }
+        }
+    }
+    // Returns every instance that came off; a stack removal only returns the instance once its last stack is gone
+    pub fn remove_aura(&mut self, id:&u32, mode: RemovalMode, definition: &AuraDefinition) -> Vec<Aura> {
+        if let RemovalMode::Stack(source) = mode {
+            let aura = self.stacking_aura(id, source, definition).map(|aura| aura.clone());
+            return match self.consume_stacks(id, source, 1, definition) {
+                Some(0) => aura.into_iter().collect(),
+                _ => vec![]
+            };
+        }
+        let aura_list = match self.auras.get_mut(id) {
+            Some(aura_list) => aura_list,
+            None => return vec![]
+        };
+        if let RemovalMode::Oldest = mode {
+            let oldest = aura_list.iter().enumerate().min_by(|(_, a), (_, b)| a.start_time.cmp(&b.start_time)).map(|(i, _)| i);
+            return oldest.map(|i| vec![aura_list.remove(i)]).unwrap_or_default();
+        }
+        let (removed, kept) = aura_list.drain(..).partition(|aura| match mode {
+            RemovalMode::Source(source) => !definition.unique_per_source || aura.source.id == source,
+            _ => true
+        });
+        *aura_list = kept;
+        removed
+    }
+    // Takes every buff, or every debuff, off the entity and returns them in aura id order
+    pub fn cleanse(&mut self, kind: &AuraKind, catalogue: &AuraCatalogue) -> Vec<Aura> {
+        let mut aura_ids:Vec<u32> = self.auras.keys().filter(|id| &catalogue.definition(id).kind == kind).cloned().collect();
+        aura_ids.sort();
+        aura_ids.into_iter().flat_map(|id| self.auras.remove(&id).unwrap_or_default()).collect()
+    }
+    // Applies the aura by its definition's rules. Returns the aura as it ends up on the entity, or None if the
+    // application was ignored
+    pub fn add_aura(&mut self, mut aura: Aura, definition: &AuraDefinition) -> Option<Aura> {
+        aura.stacks = aura.stacks.min(definition.max_stacks);
+        let aura_list = self.auras.entry(aura.id).or_default();
+        let source = aura.source.id;
+        let counts = |existing: &Aura| !definition.unique_per_source || existing.source.id == source;
+        if aura_list.iter().filter(|existing| counts(existing)).count() < definition.max_instances as usize {
+            aura_list.push(aura.clone());
+            return Some(aura);
+        }
+        // At the cap, the instance closest to falling off takes the application
+        let existing = aura_list.iter_mut().filter(|existing| counts(existing)).min_by(|a, b| a.end_time.cmp(&b.end_time))?;
+        let stacks = (existing.stacks + aura.stacks).min(definition.max_stacks);
+        match definition.refresh {
+            RefreshMode::Refresh => *existing = aura,
+            RefreshMode::Extend(ref cap) => {
+                let extended = existing.end_time.clone() + (aura.end_time.clone() - aura.start_time.clone());
+                existing.end_time = std::cmp::min(extended, aura.start_time.clone() + cap.clone());
+            },
+            RefreshMode::Ignore => return None
+        }
+        existing.stacks = stacks;
+        Some(existing.clone())
+    }
+    // The instance of the aura that stack changes go to: the source's own, or the shared one, closest to falling off
+    fn stacking_aura(&mut self, id: &u32, source: Uuid, definition: &AuraDefinition) -> Option<&mut Aura> {
+        self.auras.get_mut(id)?.iter_mut()
+            .filter(|aura| !definition.unique_per_source || aura.source.id == source)
+            .min_by(|a, b| a.end_time.cmp(&b.end_time))
+    }
+    // Returns the stacks the aura is left with, or None if the entity does not have it
+    pub fn add_stacks(&mut self, id: &u32, source: Uuid, stacks: u32, definition: &AuraDefinition) -> Option<u32> {
+        let aura = self.stacking_aura(id, source, definition)?;
+        aura.stacks = (aura.stacks + stacks).min(definition.max_stacks);
+        Some(aura.stacks)
+    }
+    // The aura falls off once its last stack is gone
+    pub fn consume_stacks(&mut self, id: &u32, source: Uuid, stacks: u32, definition: &AuraDefinition) -> Option<u32> {
+        let aura = self.stacking_aura(id, source, definition)?;
+        aura.stacks = aura.stacks.saturating_sub(stacks);
+        let left = aura.stacks;
+        if left == 0 {
+            if let Some(aura_list) = self.auras.get_mut(id) {
+                aura_list.retain(|aura| aura.stacks > 0);
+            }
+        }
+        Some(left)
+    }
+    // Stacks of the aura on the entity, from one source or from everybody
+    pub fn aura_stacks(&self, id: &u32, source: Option<Uuid>) -> u32 {
+        self.auras.get(id).map(|aura_list| aura_list.iter()
+            .filter(|aura| source.is_none_or(|source| aura.source.id == source))
+            .map(|aura| aura.stacks)
+            .sum()).unwrap_or(0)
     }
     pub fn has_own_aura(&self, id:&u32) -> Option<Aura> {
         let matching_auras:Vec<Aura> = self.auras_by_id(id).into_iter().filter(|aura| aura.source.id == self.id).collect::<Vec<Aura>>();
         matching_auras.first().cloned()
     }
-    pub fn cleanup(&mut self, current_time: Moment) {
-        // Get rid of all the auras that are no longer relevant
-        self.auras.iter_mut().for_each(|(k, mut aura_list)| {
-            aura_list.retain(|o| o.end_time > current_time)
-        });
+    // Takes every aura that has run out off the entity, and returns them in aura id order
+    pub fn cleanup(&mut self, current_time: Moment) -> Vec<Aura> {
+        let mut aura_ids:Vec<u32> = self.auras.keys().cloned().collect();
+        aura_ids.sort();
+        let mut expired = vec![];
+        for aura_id in aura_ids {
+            let aura_list = self.auras.get_mut(&aura_id).unwrap();
+            let (gone, kept):(Vec<Aura>, Vec<Aura>) = aura_list.drain(..).partition(|aura| aura.end_time <= current_time);
+            *aura_list = kept;
+            expired.extend(gone);
+        }
+        expired
     }
     pub fn auras_by_id(&self, id:&u32) -> Vec<Aura> {
         self.auras.get(id).map(|r| r.clone()).or(Some(vec![])).unwrap()
@@ -179,76 +335,239 @@ impl Entity {
             status: Status::Idle {
                 start_time: Moment::new(0, 0)
             },
+            hostile: false,
             traits: vec![],
             auras: HashMap::new(),
             last_auto: Moment::new(0, 0),
-            last_tick: HashMap::new(),
+            auto_attack: None,
             statistics: HashMap::new(),
             resources: HashMap::new(),
+            health: None,
+            recasts: HashMap::new(),
             action_repository: repository,
             action_list: apl
         }
     }
 
+    // Each timer holds the moment every charge is back; an action is usable once at least one charge is
+    pub fn ready_at(&self, action: &Action) -> Moment {
+        let full = self.recasts.get(&RecastTimer::of(action)).cloned().unwrap_or_default();
+        let pending = action.recast_time.clone() * (action.max_charges as i32 - 1);
+        (full - pending).max(Moment::default())
+    }
+    // None for entities that have nothing on the global cooldown
+    pub fn gcd_ready_at(&self) -> Option<Moment> {
+        let gcd = RecastTimer::Group(GCD_COOLDOWN_GROUP);
+        match self.action_repository.iter().any(|action| RecastTimer::of(action) == gcd) {
+            true => Some(self.recasts.get(&gcd).cloned().unwrap_or_default()),
+            false => None
+        }
+    }
+    // Whether anything on the global cooldown could be used now, recast aside
+    pub fn has_usable_gcd_action(&self) -> bool {
+        let gcd = RecastTimer::Group(GCD_COOLDOWN_GROUP);
+        self.action_repository.iter()
+            .filter(|action| RecastTimer::of(action) == gcd)
+            .any(|action| (action.available)(self) && self.can_afford(action))
+    }
+    pub fn is_ready(&self, action: &Action, moment: &Moment) -> bool {
+        &self.ready_at(action) <= moment
+    }
+    pub fn charges(&self, action: &Action, moment: &Moment) -> u32 {
+        let full = self.recasts.get(&RecastTimer::of(action)).cloned().unwrap_or_default();
+        let recast = action.recast_time.as_millis();
+        if &full <= moment || recast <= 0 {
+            return action.max_charges
+        }
+        let missing = ((full - moment.clone()).as_millis() + recast - 1) / recast;
+        action.max_charges.saturating_sub(missing as u32)
+    }
+    // Spends a charge and returns when the action can be used again
+    pub fn start_recast(&mut self, action: &Action, start: Moment) -> Moment {
+        let full = self.recasts.entry(RecastTimer::of(action)).or_default();
+        *full = std::cmp::max(full.clone(), start) + action.recast_time.clone();
+        self.ready_at(action)
+    }
     pub fn set_status(&mut self, new_status: Status) {
         self.status = new_status;
     }
+    // Declares a resource such as MP or TP; declaring it again starts it over
+    pub fn add_resource(&mut self, name: &str, max_value: u32, initial_value: u32) {
+        self.resources.insert(name.to_string(), Resource::new(name, initial_value, max_value));
+    }
+    pub fn resource(&self, name: &str) -> Option<u32> {
+        self.resources.get(name).map(|resource| resource.current_value)
+    }
+    pub fn max_resource(&self, name: &str) -> Option<u32> {
+        self.resources.get(name).map(|resource| resource.max_value)
+    }
+    pub fn set_resource_regen(&mut self, name: &str, per_tick: i32) {
+        if let Some(resource) = self.resources.get_mut(name) {
+            resource.regen = per_tick;
+        }
+    }
+    // Whether the next server tick would change any resource; the dead don't regenerate
+    pub fn regenerates(&self) -> bool {
+        self.is_alive() && self.resources.values().any(|resource| resource.regen_step() != 0)
+    }
+    // What the entity's resources do on a server tick, in resource name order so that runs replay identically
+    pub fn process_regen(&self) -> Vec<Effect> {
+        if !self.is_alive() {
+            return vec![]
+        }
+        let mut resources:Vec<&Resource> = self.resources.values().filter(|resource| resource.regen_step() != 0).collect();
+        resources.sort_by(|a, b| a.name.cmp(&b.name));
+        resources.into_iter().map(|resource| Effect::ModifyResource {
+            target: self.clone(),
+            resource: resource.name.clone(),
+            amount: resource.regen_step()
+        }).collect()
+    }
+    // Resources the entity never declared are not tracked, so they never hold an action back
+    pub fn can_afford(&self, action: &Action) -> bool {
+        self.is_alive() && action.costs.iter().all(|(name, cost)| self.resource(name).is_none_or(|value| value >= *cost))
+    }
+    fn cost_effects(&self, action: &Action) -> Vec<Effect> {
+        action.costs.iter().filter(|(name, _)| self.resources.contains_key(name)).map(|(name, cost)| Effect::ModifyResource {
+            target: self.clone(),
+            resource: name.clone(),
+            amount: -((*cost).min(i32::MAX as u32) as i32)
+        }).collect()
+    }
     pub fn modify_resource(&mut self, resource_name: String, amount: i32) {
         self.resources.get_mut(&resource_name).map(|ref mut resource| {
             resource.modify(amount)
         });
     }
+    // What the auras on the entity do on a server tick at this moment. An aura ticks on every server tick after
+    // it went up, up to and including the one it falls off on. Aura ids are walked in order so that runs replay identically
     pub fn process_dots(&self, moment: Moment) -> Vec<Effect> {
-        // Cycle through auras and find DoTs.
-        self.auras.iter().fold(vec![], |current_effects, (aura_id, aura)| {
-            aura.iter().fold(current_effects, |current_effects, aura| {
-                aura.effects.iter().map(|effect| {
-                    match effect {
-                        AuraEffect::DoT { id, ticks, potency, skill_type, r#type } => {
-                            let mut ticks_guard = ticks.write().unwrap();
-                            match ticks_guard.first() {
-                                Some(next_tick) => match next_tick < &moment {
-                                    true => {
-                                        // Convert the tick to damage
-                                        ticks_guard.remove(0);
-                                        Some(Effect::Damage {
-                                            periodic: true,
-                                            potency: potency.clone(),
-                                            source: aura.source.clone(),
-                                            target: aura.target.clone(),
-                                            skill_type: skill_type.clone(),
-                                            r#type: r#type.clone(),
-                                            action: aura.id.clone()
-                                        })
-                                    },
-                                    _ => None
-                                },
-                                _ => None
-                            }
-                        },
-                        _ => None
-                    }
-                }).filter(|r| r.is_some()).map(|r| r.unwrap()).collect()
-            })
+        let mut aura_ids:Vec<&u32> = self.auras.keys().collect();
+        aura_ids.sort();
+        aura_ids.into_iter()
+            .flat_map(|aura_id| self.auras[aura_id].iter())
+            .filter(|aura| aura.start_time < moment && moment <= aura.end_time)
+            .flat_map(|aura| aura.effects.iter().filter_map(move |effect| match effect {
+                AuraEffect::DoT { potency, skill_type, r#type } => Some(Effect::Damage {
+                    periodic: true,
+                    potency: *potency,
+                    source: aura.source.clone(),
+                    target: aura.target.clone(),
+                    skill_type: skill_type.clone(),
+                    r#type: r#type.clone(),
+                    action: aura.id
+                }),
+                AuraEffect::HoT { amount } => Some(Effect::Heal {
+                    source: aura.source.clone(),
+                    target: aura.target.clone(),
+                    amount: *amount
+                }),
+                _ => None
+            }))
+            .collect()
+    }
+    // Whether any aura on the entity still has ticks to come after this moment
+    pub fn has_periodic_auras(&self, moment: &Moment) -> bool {
+        self.auras.values().flatten().any(|aura| &aura.end_time > moment && aura.effects.iter().any(|effect| effect.is_periodic()))
+    }
+    // Weapon delay after haste, or None for entities that do not auto-attack
+    // Never shorter than a millisecond, so that enough haste can't have every swing land at once
+    pub fn auto_delay(&self) -> Option<Moment> {
+        self.auto_attack.as_ref().map(|auto| {
+            Moment::from_millis((auto.delay.as_millis() * i64::from(100 - self.haste()) / 100).max(1))
         })
     }
-    pub fn potency_modifier(&self, skill_id: &u32, base_potency: u32) -> u32 {
-        base_potency
+    // Swings at the first living opponent, in the same stable order the APL sees its candidates
+    pub fn auto_attack_effects(&self, entities: &HashMap<Uuid, Entity>) -> Vec<Effect> {
+        let auto = match (self.is_alive(), &self.auto_attack) {
+            (true, Some(auto)) => auto,
+            _ => return vec![]
+        };
+        let mut opponents:Vec<&Entity> = entities.values().filter(|v| v.hostile != self.hostile && v.is_alive()).collect();
+        opponents.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        opponents.first().map(|target| vec![Effect::Damage {
+            source: self.clone(),
+            target: (*target).clone(),
+            action: AUTO_ATTACK,
+            potency: auto.potency,
+            skill_type: SkillType::Auto,
+            r#type: auto.r#type.clone(),
+            periodic: false
+        }]).unwrap_or_default()
+    }
+    // Haste from every aura on the entity, in percent; each stack counts in full
+    pub fn haste(&self) -> u32 {
+        self.auras.values().flatten().flat_map(|aura| aura.effects.iter().map(move |effect| match effect {
+            AuraEffect::Haste { modifier } => *modifier * aura.stacks,
+            _ => 0
+        })).sum::<u32>().min(100)
     }
-    pub fn get_extra_ability_dhc(&self, d_type: &DamageType, skill_type: &SkillType, skill_id: &u32) -> f64 {
-        1.0
+    // Each aura id counts once, through its strongest instance; separate auras multiply together. Stacks scale
+    // an instance's modifiers, so three stacks of +10% make +30%
+    fn aura_multiplier(&self, modifier_of: impl Fn(&AuraEffect) -> Option<i32>) -> f64 {
+        let mut aura_ids:Vec<&u32> = self.auras.keys().collect();
+        aura_ids.sort();
+        aura_ids.into_iter().map(|aura_id| {
+            self.auras[aura_id].iter().map(|aura| {
+                aura.effects.iter().filter_map(&modifier_of)
+                    .map(|modifier| (100.0 + f64::from(modifier) * f64::from(aura.stacks)) / 100.0)
+                    .product::<f64>()
+            }).fold(1.0, f64::max)
+        }).product()
     }
-    pub fn get_extra_ability_chc(&self, d_type: &DamageType, skill_type: &SkillType, skill_id: &u32) -> f64 {
-        1.0
+    // Flat bonuses add up across aura ids, with each id counting once through its strongest instance, stacks included
+    fn aura_sum(&self, modifier_of: impl Fn(&AuraEffect) -> Option<i32>) -> i32 {
+        let mut aura_ids:Vec<&u32> = self.auras.keys().collect();
+        aura_ids.sort();
+        aura_ids.into_iter().map(|aura_id| {
+            self.auras[aura_id].iter().map(|aura| {
+                aura.effects.iter().filter_map(&modifier_of).sum::<i32>() * aura.stacks as i32
+            }).max().unwrap_or(0)
+        }).sum()
     }
-    pub fn modify_damage_from_ability(&self, skill_id: &u32, d_type: &DamageType, skill_type: &SkillType, base_damage: u32) -> u32 {
-        base_damage
+    fn has_aura_effect(&self, check: impl Fn(&AuraEffect) -> bool) -> bool {
+        self.auras.values().flatten().any(|aura| aura.effects.iter().any(&check))
     }
-    pub fn get_traits_for_ability_damage(&self, d_type: &DamageType, skill_type: &SkillType, ability_id: u32) -> f64 {
-        1.0
+    pub fn potency_modifier(&self, _skill_id: &u32, d_type: &DamageType, skill_type: &SkillType, base_potency: u32) -> u32 {
+        let bonus = self.aura_sum(|effect| effect.potency_modifier(d_type, skill_type));
+        (base_potency as i32 + bonus).max(0) as u32
+    }
+    // Percent points on top of the base rate, from the source's buffs and the target's debuffs
+    pub fn get_extra_ability_dhc(&self, target: &Entity, d_type: &DamageType, skill_type: &SkillType, _skill_id: &u32) -> f64 {
+        let own = self.aura_sum(|effect| effect.direct_hit_rate(d_type, skill_type));
+        let taken = target.aura_sum(|effect| effect.direct_hit_rate_taken(d_type, skill_type));
+        f64::from(own + taken)
+    }
+    pub fn get_extra_ability_chc(&self, target: &Entity, d_type: &DamageType, skill_type: &SkillType, _skill_id: &u32) -> f64 {
+        let own = self.aura_sum(|effect| effect.critical_hit_rate(d_type, skill_type));
+        let taken = target.aura_sum(|effect| effect.critical_hit_rate_taken(d_type, skill_type));
+        f64::from(own + taken)
+    }
+    pub fn guaranteed_critical_hit(&self, d_type: &DamageType, skill_type: &SkillType) -> bool {
+        self.has_aura_effect(|effect| effect.guarantees_critical_hit(d_type, skill_type))
+    }
+    pub fn guaranteed_direct_hit(&self, d_type: &DamageType, skill_type: &SkillType) -> bool {
+        self.has_aura_effect(|effect| effect.guarantees_direct_hit(d_type, skill_type))
+    }
+    pub fn modify_damage_from_ability(&self, _skill_id: &u32, d_type: &DamageType, skill_type: &SkillType, base_damage: u32) -> u32 {
+        let multiplier = self.aura_multiplier(|effect| effect.damage_modifier(d_type, skill_type));
+        (base_damage as f64 * multiplier).floor() as u32
+    }
+    pub fn get_traits_for_ability_damage(&self, d_type: &DamageType, skill_type: &SkillType, _ability_id: u32) -> f64 {
+        self.traits.iter().flat_map(|r#trait| r#trait.effects.iter())
+            .filter_map(|effect| effect.damage_modifier(d_type, skill_type))
+            .map(|modifier| (100.0 + modifier as f64) / 100.0)
+            .product()
+    }
+    // Debuffs such as vulnerability up, applied by the damage strategy on the receiving end
+    pub fn damage_taken_modifier(&self, d_type: &DamageType, skill_type: &SkillType) -> f64 {
+        self.aura_multiplier(|effect| effect.damage_taken(d_type, skill_type))
     }
     pub fn effects_at(&self, moment: Moment, entities: &HashMap<Uuid, Entity>) -> Result<Vec<Effect>, SimError> {
         let mut new_effects = vec![];
+        if !self.is_alive() {
+            return Ok(new_effects)
+        }
 
         if let Status::AnimationLocked { ref action, ref start_time, ref end_time } = &self.status {
             match end_time <= &moment {
@@ -264,7 +583,16 @@ impl Entity {
         if let Status::Casting { ref source, ref target, ref spell, ref start_time, ref end_time } = &self.status {
             match end_time <= &moment {
                 true => {
-                    let mut effects = (spell.effect)(source, vec![target]);
+                    new_effects.push(Effect::FinishCast {
+                        source: *source.clone(),
+                        target: *target.clone(),
+                        action: spell.clone()
+                    });
+                    // Resources are checked when the cast starts and spent when it goes off
+                    new_effects.append(&mut self.cost_effects(spell));
+                    // The effects see the caster and target as they are now, not as they were when the cast began
+                    let target = entities.get(&target.id).unwrap_or(target);
+                    let mut effects = (spell.effect)(self, vec![target]);
                     new_effects.append(&mut effects);
                     match spell.animation_delay {
                         Some(ref delay) => new_effects.push(Effect::BeginAnimationLock {
@@ -285,20 +613,27 @@ impl Entity {
         if let Status::Idle { ref start_time } = &self.status {
             match start_time <= &moment {
                 true => {
-                    // Go through the APL, see what we can do
+                    // Go through the APL, see what we can do. Candidates come in a stable order so that
+                    // selectors pick the same targets on every replay
+                    let mut candidates:Vec<&Entity> = entities.values().filter(|v| v.is_alive()).collect();
+                    candidates.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
                     let repository = Arc::clone(&self.action_repository);
                     match self.action_list.iter().fold(None, move |state, next_action| {
                         state.or_else(|| {
                             if let ConditionalAction::Cast { ref spell, ref selector } = &next_action {
                                 return repository.iter().filter(|i| i.id == *spell).collect::<Vec<&Action>>().first().cloned().and_then(|action| {
-                                    match (action.available)(&self) {
+                                    match (action.available)(&self) && self.is_ready(action, &moment) && self.can_afford(action) {
                                         true => {
-                                            (selector)(&self, entities.iter().map(|(k, v)| v).collect())
+                                            (selector)(&self, candidates.clone())
                                             .and_then(|r| {
                                                 let cast_time = (action.cast_time)(self);
                                                 match cast_time > Moment::new(0, 0) {
                                                     // We have a reference to an entity to cast on, and a spell. Let's go
-                                                    true => Some(vec![Effect::BeginCast {
+                                                    true => Some(vec![Effect::StartRecast {
+                                                        source: self.clone(),
+                                                        action: action.clone(),
+                                                        start: moment.clone()
+                                                    }, Effect::BeginCast {
                                                         source: self.clone(),
                                                         target: entities.get(&r).unwrap().clone(),
                                                         action: action.clone(),
@@ -306,7 +641,17 @@ impl Entity {
                                                     }]),
                                                     false => {
                                                         // Instant case. We instantly process the cast effects and return this + animation lock
-                                                        let mut action_effects = (action.effect)(self, vec![entities.get(&r).unwrap()]);
+                                                        let mut action_effects = vec![Effect::StartRecast {
+                                                            source: self.clone(),
+                                                            action: action.clone(),
+                                                            start: moment.clone()
+                                                        }, Effect::FinishCast {
+                                                            source: self.clone(),
+                                                            target: entities.get(&r).unwrap().clone(),
+                                                            action: action.clone()
+                                                        }];
+                                                        action_effects.append(&mut self.cost_effects(action));
+                                                        action_effects.append(&mut (action.effect)(self, vec![entities.get(&r).unwrap()]));
                                                         match action.animation_delay {
                                                             Some(ref delay) => action_effects.push(Effect::BeginAnimationLock {
                                                                 target: self.clone(),
@@ -344,4 +689,173 @@ impl Entity {
         }
         Ok(new_effects)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::{Entity, AutoAttack};
+    use crate::{AuraEffect, DamageType, Aura, AuraCatalogue, AuraDefinition, AuraKind, RefreshMode, RemovalMode, Moment};
+    use std::sync::Arc;
+
+    fn aura(source: &Entity, target: &Entity, start: i32, duration: i32) -> Aura {
+        Aura {
+            id: 1,
+            source: source.clone(),
+            target: target.clone(),
+            start_time: Moment::new(start, 0),
+            end_time: Moment::new(start + duration, 0),
+            effects: vec![],
+            stacks: 1
+        }
+    }
+    fn end_times(entity: &Entity) -> Vec<Moment> {
+        let mut ends:Vec<Moment> = entity.auras_by_id(&1).into_iter().map(|aura| aura.end_time).collect();
+        ends.sort();
+        ends
+    }
+
+    #[test]
+    fn auras_follow_their_definition() {
+        let bard = Entity::create("bard".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let dragoon = Entity::create("dragoon".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let fresh = || Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![]));
+
+        // Refreshing replaces the instance
+        let mut target = fresh();
+        let definition = AuraDefinition::new(1);
+        target.add_aura(aura(&bard, &target, 0, 10), &definition);
+        assert_eq!(target.add_aura(aura(&bard, &target, 5, 10), &definition).map(|aura| aura.end_time), Some(Moment::new(15, 0)));
+        assert_eq!(end_times(&target), vec![Moment::new(15, 0)]);
+
+        // Past the cap, the instance closest to falling off takes the application
+        let mut target = fresh();
+        let definition = AuraDefinition::new(1).with_max_instances(2);
+        for start in 0..3 {
+            target.add_aura(aura(&bard, &target, start, 10), &definition);
+        }
+        assert_eq!(end_times(&target), vec![Moment::new(11, 0), Moment::new(12, 0)]);
+
+        // Extending adds up to the cap
+        let mut target = fresh();
+        let definition = AuraDefinition::new(1).with_refresh(RefreshMode::Extend(Moment::new(12, 0)));
+        target.add_aura(aura(&bard, &target, 0, 10), &definition);
+        target.add_aura(aura(&bard, &target, 5, 10), &definition);
+        assert_eq!(end_times(&target), vec![Moment::new(17, 0)]);
+
+        let mut target = fresh();
+        let definition = AuraDefinition::new(1).with_refresh(RefreshMode::Ignore);
+        target.add_aura(aura(&bard, &target, 0, 10), &definition);
+        assert!(target.add_aura(aura(&bard, &target, 5, 10), &definition).is_none());
+        assert_eq!(end_times(&target), vec![Moment::new(10, 0)]);
+
+        // Every source keeps its own instance unless the aura is shared
+        let mut target = fresh();
+        let definition = AuraDefinition::new(1);
+        target.add_aura(aura(&bard, &target, 0, 10), &definition);
+        target.add_aura(aura(&dragoon, &target, 1, 10), &definition);
+        assert_eq!(end_times(&target).len(), 2);
+        let mut target = fresh();
+        let definition = AuraDefinition::new(1).with_unique_per_source(false);
+        target.add_aura(aura(&bard, &target, 0, 10), &definition);
+        target.add_aura(aura(&dragoon, &target, 1, 10), &definition);
+        assert_eq!(end_times(&target), vec![Moment::new(11, 0)]);
+        // Nobody owns a shared aura, so anybody can take it down
+        target.remove_aura(&1, RemovalMode::Source(bard.id), &definition);
+        assert!(end_times(&target).is_empty());
+    }
+
+    #[test]
+    fn stacks_are_added_and_consumed() {
+        let bard = Entity::create("bard".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let mut target = Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let definition = AuraDefinition::new(1).with_max_stacks(4);
+        assert_eq!(target.add_stacks(&1, bard.id, 1, &definition), None);
+        target.add_aura(aura(&bard, &target, 0, 10), &definition);
+        assert_eq!(target.add_stacks(&1, bard.id, 2, &definition), Some(3));
+        // Reapplying adds the application's stacks, up to the cap
+        target.add_aura(aura(&bard, &target, 1, 10), &definition);
+        target.add_aura(aura(&bard, &target, 2, 10), &definition);
+        assert_eq!(target.aura_stacks(&1, Some(bard.id)), 4);
+        assert_eq!(target.aura_stacks(&1, Some(target.id)), 0);
+        assert_eq!(target.consume_stacks(&1, bard.id, 3, &definition), Some(1));
+        assert_eq!(target.consume_stacks(&1, bard.id, 3, &definition), Some(0));
+        assert!(target.auras_by_id(&1).is_empty());
+    }
+
+    #[test]
+    fn removal_modes() {
+        let bard = Entity::create("bard".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let dragoon = Entity::create("dragoon".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let definition = AuraDefinition::new(1).with_max_stacks(2);
+        let target = || {
+            let mut target = Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![]));
+            target.add_aura(aura(&bard, &target, 0, 10), &definition);
+            target.add_aura(aura(&bard, &target, 1, 10), &definition);
+            target.add_aura(aura(&dragoon, &target, 2, 10), &definition);
+            target
+        };
+        let mut all = target();
+        assert_eq!(all.remove_aura(&1, RemovalMode::All, &definition).len(), 2);
+        assert!(end_times(&all).is_empty());
+
+        let mut by_source = target();
+        assert_eq!(by_source.remove_aura(&1, RemovalMode::Source(bard.id), &definition).len(), 1);
+        assert_eq!(end_times(&by_source), vec![Moment::new(12, 0)]);
+        // Nothing from a source that never applied it
+        assert!(by_source.remove_aura(&1, RemovalMode::Source(bard.id), &definition).is_empty());
+
+        // The first application, even once extending has pushed it past the others
+        let extending = AuraDefinition::new(1).with_refresh(RefreshMode::Extend(Moment::new(30, 0)));
+        let mut oldest = Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![]));
+        oldest.add_aura(aura(&bard, &oldest, 0, 10), &extending);
+        oldest.add_aura(aura(&bard, &oldest, 1, 10), &extending);
+        oldest.add_aura(aura(&dragoon, &oldest, 2, 10), &extending);
+        let removed = oldest.remove_aura(&1, RemovalMode::Oldest, &extending);
+        assert_eq!(removed[0].source.id, bard.id);
+        assert_eq!(removed[0].end_time, Moment::new(20, 0));
+        assert_eq!(end_times(&oldest), vec![Moment::new(12, 0)]);
+
+        // The bard's instance holds two stacks from the reapplication
+        let mut stacks = target();
+        assert!(stacks.remove_aura(&1, RemovalMode::Stack(bard.id), &definition).is_empty());
+        assert_eq!(stacks.aura_stacks(&1, Some(bard.id)), 1);
+        assert_eq!(stacks.remove_aura(&1, RemovalMode::Stack(bard.id), &definition).len(), 1);
+        assert_eq!(end_times(&stacks), vec![Moment::new(12, 0)]);
+        // Expiry takes what is left
+        assert_eq!(stacks.cleanup(Moment::new(12, 0)).len(), 1);
+        assert!(end_times(&stacks).is_empty());
+    }
+
+    #[test]
+    fn haste_never_takes_the_swing_delay_to_nothing() {
+        let mut monk = Entity::create("monk".to_string(), None, 70, vec![], Arc::new(vec![]));
+        monk.auto_attack = Some(AutoAttack {
+            delay: Moment::new(3, 0),
+            potency: 110,
+            r#type: DamageType::Blunt
+        });
+        monk.add_aura(Aura {
+            effects: vec![AuraEffect::Haste { modifier: 150 }],
+            ..aura(&monk, &monk, 0, 10)
+        }, &AuraDefinition::new(1));
+        assert_eq!(monk.auto_delay(), Some(Moment::from_millis(1)));
+    }
+
+    #[test]
+    fn cleansing_goes_by_kind() {
+        let bard = Entity::create("bard".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let mut target = Entity::create("big_bad".to_string(), None, 70, vec![], Arc::new(vec![]));
+        let catalogue = AuraCatalogue::new()
+            .with_definition(AuraDefinition::new(1).with_kind(AuraKind::Debuff))
+            .with_definition(AuraDefinition::new(2));
+        target.add_aura(aura(&bard, &target, 0, 10), &catalogue.definition(&1));
+        target.add_aura(Aura { id: 2, ..aura(&bard, &target, 0, 10) }, &catalogue.definition(&2));
+        let removed = target.cleanse(&AuraKind::Debuff, &catalogue);
+        assert_eq!(removed.iter().map(|aura| aura.id).collect::<Vec<u32>>(), vec![1]);
+        assert!(target.auras_by_id(&1).is_empty());
+        assert_eq!(target.auras_by_id(&2).len(), 1);
+        // Auras missing from the catalogue count as buffs
+        target.add_aura(Aura { id: 3, ..aura(&bard, &target, 0, 10) }, &catalogue.definition(&3));
+        assert_eq!(target.cleanse(&AuraKind::Buff, &catalogue).len(), 2);
+        assert!(target.cleanse(&AuraKind::Buff, &catalogue).is_empty());
+    }
+}
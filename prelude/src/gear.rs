@@ -0,0 +1,305 @@
+use super::{Entity, Job, primary_stat, is_caster, level_main, level_sub};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Slot {
+    Weapon,
+    OffHand,
+    Head,
+    Body,
+    Hands,
+    Waist,
+    Legs,
+    Feet,
+    Earrings,
+    Necklace,
+    Bracelets,
+    LeftRing,
+    RightRing
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Clan {
+    Midlander,
+    Highlander,
+    Wildwood,
+    Duskwight,
+    Plainsfolk,
+    Dunesfolk,
+    SeekerOfTheSun,
+    KeeperOfTheMoon,
+    SeaWolf,
+    Hellsguard,
+    Raen,
+    Xaela
+}
+
+const MAIN_STATS: [&str; 5] = ["Strength", "Dexterity", "Vitality", "Intelligence", "Mind"];
+// The statistics materia exist for
+const SECONDARY_STATS: [&str; 7] = ["Critical Hit Rate", "Direct Hit Rate", "Determination", "Skill Speed", "Spell Speed", "Tenacity", "Piety"];
+
+impl Clan {
+    // Added to the level's base value, in the order of MAIN_STATS
+    fn modifiers(&self) -> [i32; 5] {
+        match self {
+            Clan::Midlander => [2, -1, 0, 3, -1],
+            Clan::Highlander => [3, 0, 2, -2, 0],
+            Clan::Wildwood => [0, 3, -1, 2, -1],
+            Clan::Duskwight => [0, 0, -1, 3, 1],
+            Clan::Plainsfolk => [-1, 3, -1, 2, 0],
+            Clan::Dunesfolk => [-1, 1, -2, 2, 3],
+            Clan::SeekerOfTheSun => [2, 3, 0, -1, -1],
+            Clan::KeeperOfTheMoon => [-1, 2, -1, 1, 3],
+            Clan::SeaWolf => [2, -2, 3, -1, 1],
+            Clan::Hellsguard => [0, -2, 2, 0, 3],
+            Clan::Raen => [-1, 2, 0, 0, 2],
+            Clan::Xaela => [3, 0, 2, -1, -1]
+        }
+    }
+}
+
+// Percentages of the level's base value, in the order of MAIN_STATS
+fn job_modifiers(job: &Job) -> [u32; 5] {
+    match job {
+        Job::PLD | Job::GLA => [100, 95, 110, 60, 100],
+        Job::WAR | Job::MRD => [105, 95, 110, 40, 55],
+        Job::DRK => [105, 95, 110, 60, 40],
+        Job::MNK | Job::PGL => [110, 105, 100, 50, 90],
+        Job::DRG | Job::LNC => [115, 100, 105, 45, 65],
+        Job::SAM => [112, 108, 100, 60, 50],
+        Job::NIN | Job::ROG => [85, 110, 100, 65, 75],
+        Job::BRD | Job::ARC => [90, 115, 100, 85, 80],
+        Job::MCH => [85, 115, 100, 80, 85],
+        Job::BLM | Job::THM => [45, 100, 100, 115, 75],
+        Job::SMN | Job::ACN => [60, 100, 100, 115, 80],
+        Job::RDM => [55, 105, 100, 115, 110],
+        Job::WHM | Job::CNJ => [55, 105, 100, 105, 115],
+        Job::SCH => [60, 100, 100, 105, 115],
+        Job::AST => [50, 100, 100, 105, 115]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Materia {
+    pub stat: String,
+    pub value: u32
+}
+
+impl Materia {
+    pub fn new(stat: &str, value: u32) -> Self {
+        Self {
+            stat: stat.to_string(),
+            value
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Item {
+    pub name: String,
+    pub slot: Slot,
+    pub stats: HashMap<String, u32>,
+    // No statistic can be melded past this; None means the item's highest meldable secondary statistic
+    pub meld_cap: Option<u32>
+}
+
+impl Item {
+    pub fn new(name: &str, slot: Slot) -> Self {
+        Self {
+            name: name.to_string(),
+            slot,
+            stats: HashMap::new(),
+            meld_cap: None
+        }
+    }
+    pub fn with_stat(mut self, stat: &str, value: u32) -> Self {
+        self.stats.insert(stat.to_string(), value);
+        self
+    }
+    pub fn with_meld_cap(self, cap: u32) -> Self {
+        Self {
+            meld_cap: Some(cap),
+            ..self
+        }
+    }
+    fn meld_cap(&self) -> u32 {
+        self.meld_cap.unwrap_or_else(|| {
+            self.stats.iter()
+                .filter(|(stat, _)| SECONDARY_STATS.contains(&stat.as_str()))
+                .map(|(_, value)| *value)
+                .max()
+                .unwrap_or(0)
+        })
+    }
+    // The item's statistics once melded; whatever goes past the cap is wasted
+    pub fn melded(&self, melds: &[Materia]) -> HashMap<String, u32> {
+        let cap = self.meld_cap();
+        let mut stats = self.stats.clone();
+        for meld in melds {
+            let value = stats.entry(meld.stat.clone()).or_insert(0);
+            *value = (*value + meld.value).min(cap.max(*value));
+        }
+        stats
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FoodBonus {
+    pub stat: String,
+    pub percent: u32,
+    pub cap: u32
+}
+
+#[derive(Clone, Debug)]
+pub struct Food {
+    pub name: String,
+    pub bonuses: Vec<FoodBonus>
+}
+
+impl Food {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bonuses: vec![]
+        }
+    }
+    pub fn with_bonus(mut self, stat: &str, percent: u32, cap: u32) -> Self {
+        self.bonuses.push(FoodBonus {
+            stat: stat.to_string(),
+            percent,
+            cap
+        });
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GearSet {
+    pub clan: Clan,
+    items: BTreeMap<Slot, (Item, Vec<Materia>)>,
+    food: Option<Food>
+}
+
+impl GearSet {
+    pub fn new(clan: Clan) -> Self {
+        Self {
+            clan,
+            items: BTreeMap::new(),
+            food: None
+        }
+    }
+    // Replaces whatever was in the item's slot
+    pub fn with_item(mut self, item: Item, melds: Vec<Materia>) -> Self {
+        self.items.insert(item.slot, (item, melds));
+        self
+    }
+    pub fn with_food(self, food: Food) -> Self {
+        Self {
+            food: Some(food),
+            ..self
+        }
+    }
+    pub fn item(&self, slot: Slot) -> Option<&Item> {
+        self.items.get(&slot).map(|(item, _)| item)
+    }
+    // Race and job base values, then gear and melds, then food on top of the total
+    pub fn statistics(&self, job: &Job, level: u16) -> HashMap<String, u32> {
+        let level_main = level_main(&level);
+        let level_sub = level_sub(&level);
+        let mut stats:HashMap<String, u32> = HashMap::new();
+        let clan = self.clan.modifiers();
+        for (i, (stat, job_modifier)) in MAIN_STATS.iter().zip(job_modifiers(job).iter()).enumerate() {
+            let base = (level_main * job_modifier / 100) as i32 + clan[i];
+            stats.insert(stat.to_string(), base.max(0) as u32);
+        }
+        stats.insert("Determination".to_string(), level_main);
+        stats.insert("Piety".to_string(), level_main);
+        for stat in ["Critical Hit Rate", "Direct Hit Rate", "Skill Speed", "Spell Speed", "Tenacity"].iter() {
+            stats.insert(stat.to_string(), level_sub);
+        }
+        for (item, melds) in self.items.values() {
+            for (stat, value) in item.melded(melds) {
+                *stats.entry(stat).or_insert(0) += value;
+            }
+        }
+        if let Some(ref food) = self.food {
+            for bonus in food.bonuses.iter() {
+                let value = stats.entry(bonus.stat.clone()).or_insert(0);
+                *value += (*value * bonus.percent / 100).min(bonus.cap);
+            }
+        }
+        // The damage formula reads attack power off the job's primary statistic
+        let primary = stats.get(primary_stat(job)).cloned().unwrap_or(0);
+        match is_caster(job) {
+            true => stats.insert("Magic Attack Power".to_string(), primary),
+            false => stats.insert("Attack Power".to_string(), primary)
+        };
+        stats
+    }
+    pub fn equip(&self, entity: &mut Entity) {
+        let job = match entity.job {
+            Some(ref job) => job.clone(),
+            None => return
+        };
+        for (stat, value) in self.statistics(&job, entity.level) {
+            entity.set_statistic(&stat, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GearSet, Item, Materia, Food, Clan, Slot};
+    use crate::{Entity, Job};
+    use std::sync::Arc;
+
+    fn ring() -> Item {
+        Item::new("Ring of Testing", Slot::LeftRing)
+            .with_stat("Dexterity", 100)
+            .with_stat("Critical Hit Rate", 90)
+            .with_stat("Direct Hit Rate", 63)
+    }
+
+    #[test]
+    fn melds_stop_at_the_cap() {
+        let melded = ring().melded(&[Materia::new("Direct Hit Rate", 20), Materia::new("Direct Hit Rate", 20), Materia::new("Determination", 20)]);
+        // 63 + 40 is capped at the item's best secondary, 90
+        assert_eq!(melded["Direct Hit Rate"], 90);
+        assert_eq!(melded["Determination"], 20);
+        assert_eq!(melded["Critical Hit Rate"], 90);
+        let melded = ring().with_meld_cap(70).melded(&[Materia::new("Critical Hit Rate", 20)]);
+        assert_eq!(melded["Critical Hit Rate"], 90);
+        // Defense and delay can't be melded, so they don't raise the cap
+        let melded = ring().with_stat("Defense", 500).with_stat("Delay", 300).melded(&[Materia::new("Direct Hit Rate", 40)]);
+        assert_eq!(melded["Direct Hit Rate"], 90);
+    }
+
+    #[test]
+    fn derives_the_final_statistics() {
+        let gear = GearSet::new(Clan::SeekerOfTheSun)
+            .with_item(Item::new("Bow of Testing", Slot::Weapon)
+                .with_stat("Physical Damage", 104)
+                .with_stat("Auto-attack", 95)
+                .with_stat("Dexterity", 300)
+                .with_stat("Determination", 200), vec![])
+            .with_item(ring(), vec![Materia::new("Direct Hit Rate", 20)])
+            .with_food(Food::new("Testing Pie")
+                .with_bonus("Direct Hit Rate", 10, 30)
+                .with_bonus("Determination", 10, 30));
+        let stats = gear.statistics(&Job::BRD, 70);
+        // 292 at 115%, plus 3 for the clan, plus gear
+        assert_eq!(stats["Dexterity"], 335 + 3 + 400);
+        assert_eq!(stats["Attack Power"], stats["Dexterity"]);
+        assert_eq!(stats["Physical Damage"], 104);
+        assert_eq!(stats["Critical Hit Rate"], 364 + 90);
+        // 364 + 83 = 447, 10% is 44, capped at 30
+        assert_eq!(stats["Direct Hit Rate"], 447 + 30);
+        // 292 + 200 = 492, 10% is 49, capped at 30
+        assert_eq!(stats["Determination"], 492 + 30);
+
+        let mut bard = Entity::create("bard".to_string(), Some(Job::BRD), 70, vec![], Arc::new(vec![]));
+        gear.equip(&mut bard);
+        assert_eq!(bard.get_statistic("Dexterity"), 738);
+        assert_eq!(bard.get_statistic("Skill Speed"), 364);
+    }
+}
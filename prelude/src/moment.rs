@@ -0,0 +1,267 @@
+use std::ops::{Add, AddAssign, Sub, SubAssign, Mul, Div};
+use std::convert::TryFrom;
+use std::iter::Sum;
+use std::time::Duration;
+use std::error::Error;
+use std::fmt::{Formatter, Display, Error as FmtError};
+use std::str::FromStr;
+
+// A point in (or length of) fight time. Values are always normalised so that 0 <= m < 1000;
+// negative moments carry their sign on s, so -0.5s is { s: -1, m: 500 }.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Moment {
+    s: i32,
+    m: i32
+}
+// `{}` prints seconds with three decimals, as s.mmm; the alternate `{:#}` prints mm:ss.mmm
+impl Display for Moment {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), FmtError> {
+        let total_ms = self.as_millis();
+        let sign = match total_ms < 0 {
+            true => "-",
+            false => ""
+        };
+        let (s, m) = (total_ms.abs() / 1000, total_ms.abs() % 1000);
+        match formatter.alternate() {
+            true => write!(formatter, "{}{:02}:{:02}.{:03}", sign, s / 60, s % 60, m),
+            false => write!(formatter, "{}{}.{:03}", sign, s, m)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseMomentError {
+    input: String
+}
+impl Display for ParseMomentError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(formatter, "cannot read \"{}\" as a moment", self.input)
+    }
+}
+impl Error for ParseMomentError {}
+
+// Reads "2.5" and "2.500s" as seconds, "2500ms" as milliseconds and "1:02.300" as minutes and seconds.
+// After a colon the seconds always take two digits, so that "1:30" and "1:300" cannot be mistaken for each other.
+impl FromStr for Moment {
+    type Err = ParseMomentError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMomentError {
+            input: input.to_string()
+        };
+        let digits = |part: &str| -> Result<i32, ParseMomentError> {
+            match !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) {
+                true => part.parse::<i32>().map_err(|_| error()),
+                false => Err(error())
+            }
+        };
+        // Seconds with up to three decimals, as milliseconds
+        let seconds = |part: &str| -> Result<i32, ParseMomentError> {
+            let mut halves = part.splitn(2, '.');
+            let whole = digits(halves.next().unwrap_or(""))?;
+            let fraction = match halves.next() {
+                Some(fraction) if fraction.len() <= 3 => digits(fraction)? * 10i32.pow(3 - fraction.len() as u32),
+                Some(_) => return Err(error()),
+                None => 0
+            };
+            whole.checked_mul(1000).and_then(|ms| ms.checked_add(fraction)).ok_or_else(error)
+        };
+        let trimmed = input.trim();
+        let total_ms = if let Some(ms) = trimmed.strip_suffix("ms") {
+            digits(ms.trim())?
+        } else if let Some(s) = trimmed.strip_suffix('s') {
+            seconds(s.trim())?
+        } else if let Some(colon) = trimmed.find(':') {
+            let (left, right) = (&trimmed[..colon], &trimmed[colon + 1..]);
+            if right.split('.').next().map(|whole| whole.len()) != Some(2) {
+                return Err(error())
+            }
+            let second_ms = seconds(right)?;
+            if second_ms >= 60_000 {
+                return Err(error())
+            }
+            digits(left)?.checked_mul(60_000).and_then(|ms| ms.checked_add(second_ms)).ok_or_else(error)?
+        } else {
+            seconds(trimmed)?
+        };
+        Ok(Moment::new(total_ms / 1000, total_ms % 1000))
+    }
+}
+impl Moment {
+    pub fn new(s:i32, m:i32) -> Self {
+        Self::from_millis(i64::from(s) * 1000 + i64::from(m))
+    }
+    pub fn from_millis(ms: i64) -> Self {
+        Self {
+            s: ms.div_euclid(1000) as i32,
+            m: ms.rem_euclid(1000) as i32
+        }
+    }
+    // Whole seconds, rounded down, so negative moments carry their sign here
+    pub fn seconds(&self) -> i32 {
+        self.s
+    }
+    // Always in [0, 1000)
+    pub fn millis(&self) -> i32 {
+        self.m
+    }
+    pub fn as_millis(&self) -> i64 {
+        i64::from(self.s) * 1000 + i64::from(self.m)
+    }
+    pub fn as_secs_f64(&self) -> f64 {
+        self.as_millis() as f64 / 1000.0
+    }
+    pub fn is_negative(&self) -> bool {
+        self.s < 0
+    }
+    // Never goes below zero
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self::from_millis((self.as_millis() - other.as_millis()).max(0))
+    }
+    // Never goes past the largest representable moment
+    pub fn saturating_add(self, other: Self) -> Self {
+        let max_ms = i64::from(i32::MAX) * 1000 + 999;
+        Self::from_millis((self.as_millis() + other.as_millis()).min(max_ms))
+    }
+}
+impl Add for Moment {
+    type Output = Moment;
+    fn add(self, other: Self) -> Self {
+        Self::from_millis(self.as_millis() + other.as_millis())
+    }
+}
+impl AddAssign for Moment {
+    fn add_assign(&mut self, other: Self) {
+        *self = self.clone() + other;
+    }
+}
+impl Sub for Moment {
+    type Output = Moment;
+    fn sub(self, other: Self) -> Self {
+        Self::from_millis(self.as_millis() - other.as_millis())
+    }
+}
+impl SubAssign for Moment {
+    fn sub_assign(&mut self, other: Self) {
+        *self = self.clone() - other;
+    }
+}
+// Scaling rounds to the nearest millisecond
+impl Mul<f64> for Moment {
+    type Output = Moment;
+    fn mul(self, factor: f64) -> Self {
+        Self::from_millis((self.as_millis() as f64 * factor).round() as i64)
+    }
+}
+impl Div<f64> for Moment {
+    type Output = Moment;
+    fn div(self, divisor: f64) -> Self {
+        Self::from_millis((self.as_millis() as f64 / divisor).round() as i64)
+    }
+}
+impl Mul<i32> for Moment {
+    type Output = Moment;
+    fn mul(self, factor: i32) -> Self {
+        Self::from_millis(self.as_millis() * i64::from(factor))
+    }
+}
+impl Sum for Moment {
+    fn sum<I: Iterator<Item = Moment>>(iter: I) -> Self {
+        iter.fold(Moment::default(), |total, moment| total + moment)
+    }
+}
+impl<'a> Sum<&'a Moment> for Moment {
+    fn sum<I: Iterator<Item = &'a Moment>>(iter: I) -> Self {
+        iter.fold(Moment::default(), |total, moment| total + moment.clone())
+    }
+}
+impl From<Duration> for Moment {
+    fn from(duration: Duration) -> Self {
+        Self::from_millis(duration.as_millis() as i64)
+    }
+}
+impl TryFrom<Moment> for Duration {
+    type Error = Moment;
+    // Negative moments have no Duration equivalent and are handed back
+    fn try_from(moment: Moment) -> Result<Self, Self::Error> {
+        match moment.is_negative() {
+            true => Err(moment),
+            false => Ok(Duration::from_millis(moment.as_millis() as u64))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Moment;
+    use std::convert::TryFrom;
+    use std::time::Duration;
+
+    #[test]
+    fn adds_ok() {
+        let new_time = Moment::new(3, 400) + Moment::new(0, 400);
+        assert_eq!(new_time.seconds(), 3);
+        assert_eq!(new_time.millis(), 800);
+        let new_time_with_overflow = Moment::new(3, 400) + Moment::new(2, 900);
+        assert_eq!(new_time_with_overflow.seconds(), 6);
+        assert_eq!(new_time_with_overflow.millis(), 300);
+    }
+    #[test]
+    fn normalises_on_construction() {
+        assert_eq!(Moment::new(1, 2500), Moment::new(3, 500));
+        assert_eq!(Moment::new(2, -500), Moment::new(1, 500));
+        let negative = Moment::new(0, -500);
+        assert_eq!((negative.seconds(), negative.millis()), (-1, 500));
+        assert_eq!(Moment::from_millis(-1500).as_millis(), -1500);
+        assert_eq!(Moment::new(1, 1000), Moment::new(2, 0));
+    }
+    #[test]
+    fn arithmetic() {
+        assert_eq!(Moment::new(3, 100) - Moment::new(1, 400), Moment::new(1, 700));
+        assert_eq!(Moment::new(1, 0) - Moment::new(1, 500), Moment::from_millis(-500));
+        assert_eq!(Moment::new(1, 0).saturating_sub(Moment::new(1, 500)), Moment::default());
+        assert_eq!(Moment::new(2, 500) * 0.8, Moment::new(2, 0));
+        assert_eq!(Moment::new(2, 500) / 2.0, Moment::new(1, 250));
+        assert_eq!(Moment::new(2, 500) * 3, Moment::new(7, 500));
+        let mut time = Moment::new(1, 0);
+        time += Moment::new(0, 750);
+        time -= Moment::new(0, 250);
+        assert_eq!(time, Moment::new(1, 500));
+        let total:Moment = [Moment::new(1, 500), Moment::new(0, 600)].iter().sum();
+        assert_eq!(total, Moment::new(2, 100));
+        assert_eq!(Moment::new(i32::MAX, 900).saturating_add(Moment::new(1, 0)), Moment::new(i32::MAX, 999));
+    }
+    #[test]
+    fn orders_and_converts() {
+        let mut times = vec![Moment::new(2, 0), Moment::from_millis(-10), Moment::new(1, 999)];
+        times.sort();
+        assert_eq!(times, vec![Moment::from_millis(-10), Moment::new(1, 999), Moment::new(2, 0)]);
+        assert_eq!(Moment::from(Duration::from_millis(2500)), Moment::new(2, 500));
+        assert_eq!(Duration::try_from(Moment::new(2, 500)), Ok(Duration::from_millis(2500)));
+        assert!(Duration::try_from(Moment::from_millis(-1)).is_err());
+        assert_eq!(Moment::new(2, 500).as_secs_f64(), 2.5);
+    }
+    #[test]
+    fn displays_both_formats() {
+        assert_eq!(format!("{}", Moment::new(2, 50)), "2.050");
+        assert_eq!(format!("{:#}", Moment::new(62, 300)), "01:02.300");
+        assert_eq!(format!("{:#}", Moment::new(0, 5)), "00:00.005");
+        assert_eq!(format!("{}", Moment::from_millis(-1500)), "-1.500");
+    }
+    #[test]
+    fn parses_every_format() {
+        assert_eq!("2.5".parse::<Moment>(), Ok(Moment::new(2, 500)));
+        assert_eq!("2.500s".parse::<Moment>(), Ok(Moment::new(2, 500)));
+        assert_eq!("1:02.300".parse::<Moment>(), Ok(Moment::new(62, 300)));
+        assert_eq!("2500ms".parse::<Moment>(), Ok(Moment::new(2, 500)));
+        assert_eq!("3".parse::<Moment>(), Ok(Moment::new(3, 0)));
+        assert_eq!("1:05".parse::<Moment>(), Ok(Moment::new(65, 0)));
+        // Whatever Display prints reads back
+        for moment in [Moment::new(2, 50), Moment::new(75, 999)] {
+            assert_eq!(format!("{}", moment).parse::<Moment>(), Ok(moment.clone()));
+            assert_eq!(format!("{:#}", moment).parse::<Moment>(), Ok(moment.clone()));
+        }
+        for bad in ["", "abc", "2.5000", "1:75.000", "-1", "2.5.5", "ms", "1:", "1:300", "1:030", "1:5"] {
+            assert!(bad.parse::<Moment>().is_err(), "{} should not parse", bad);
+        }
+    }
+}
\ No newline at end of file
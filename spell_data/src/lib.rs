@@ -6,6 +6,7 @@ use std::error::Error;
 
 use serde::de::{Deserialize, Deserializer, Unexpected};
 use serde_repr::{Deserialize_repr};
+use simxiv_prelude::{Action, Moment, GCD_COOLDOWN_GROUP};
 
 pub type ActionId = u32;
 
@@ -75,6 +76,25 @@ pub struct RawAction {
     pub cast: u32,
     #[serde(rename="Recast<100ms>")]
     pub recast: u32,
+    pub cooldown_group: u32,
+}
+
+impl RawAction {
+    // Carries over the timings, the cooldown group and the cost, as "MP" or "TP"; effects, charges and animation
+    // locks are up to the caller. Costs are taken as the sheet has them, so scaling mana costs to a job's pool
+    // is up to the caller too
+    pub fn to_action(&self) -> Action {
+        let action = Action::new(self.id, Moment::from_millis(i64::from(self.cast) * 100))
+            .with_recast_time(Moment::from_millis(i64::from(self.recast) * 100))
+            .with_off_gcd(self.cooldown_group != GCD_COOLDOWN_GROUP)
+            .with_cooldown_group(self.cooldown_group);
+        match (&self.cost_type, self.cost) {
+            (_, 0) => action,
+            (CostType::Known(KnownCost::Mana), cost) => action.with_cost("MP", cost),
+            (CostType::Known(KnownCost::Tact), cost) => action.with_cost("TP", cost),
+            (CostType::Unknown(_), _) => action
+        }
+    }
 }
 
 pub fn load_actions<P: AsRef<Path>>(path: P) -> Result<HashMap<ActionId, RawAction>, Box<dyn Error>> {
@@ -3,6 +3,7 @@ extern crate simxiv_prelude;
 
 use std::path::PathBuf;
 use simxiv_spelldata::{load_actions, RawAction, Range, CostType, KnownCost};
+use simxiv_prelude::Moment;
 
 #[test]
 fn it_works() {
@@ -21,6 +22,7 @@ fn it_works() {
         can_target_hostile: true,
         cast: 0,
         recast: 25,
+        cooldown_group: 58,
     });
 
     let veraero = data.get(&7507).unwrap();
@@ -35,5 +37,15 @@ fn it_works() {
         can_target_hostile: true,
         cast: 50,
         recast: 25,
+        cooldown_group: 58,
     });
+
+    assert_eq!(veraero.to_action().costs, vec![("MP".to_string(), 4)]);
+    assert_eq!(bootshine.to_action().costs, vec![("TP".to_string(), 50)]);
+
+    let corps_a_corps = data.get(&7506).unwrap().to_action();
+    assert_eq!(corps_a_corps.recast_time, Moment::new(40, 0));
+    assert_eq!(corps_a_corps.cooldown_group, Some(6));
+    assert!(corps_a_corps.off_gcd);
+    assert!(!veraero.to_action().off_gcd);
 }